@@ -0,0 +1,123 @@
+//! Instructions per second for the interpreter's hot paths. Run with
+//! `cargo bench`, or e.g. `cargo bench -- execute` for a single group.
+
+use std::fs;
+
+use chip8::{
+    audio::NullSink,
+    framebuffer::Framebuffer,
+    frontend::Headless,
+    platform::Quirks,
+    vm::{Engine, Options, Vm},
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+const FRAMES: u64 = 100;
+const SPEED: u32 = 10_000;
+
+// ROMs that keep running without input, rather than ending in a jump to
+// itself or waiting for a key, so every frame runs `SPEED` instructions.
+const ROMS: [&str; 4] = [
+    "Particle Demo [zeroZshadow, 2008]",
+    "Blinky [Hans Christian Egeberg, 1991]",
+    "Trip8 Demo (2008) [Revival Studios]",
+    "Brix [Andreas Gustafsson, 1990]",
+];
+
+fn rom(name: &str) -> Vec<u8> {
+    let path = format!("{}/examples/{name}.ch8", env!("CARGO_MANIFEST_DIR"));
+    fs::read(&path).unwrap_or_else(|e| panic!("could not read {path}: {e}"))
+}
+
+/// A VM running `FRAMES` frames of `SPEED` instructions. Without the display
+/// wait quirk every frame runs all of them.
+fn vm(rom: Vec<u8>, engine: Engine) -> Vm {
+    let options = Options {
+        quirks: Some(Quirks::default()),
+        speed: Some(SPEED),
+        seed: Some(0),
+        engine,
+        ..Options::default()
+    };
+    Vm::new(
+        rom,
+        Box::new(Headless::new(Some(FRAMES))),
+        Box::new(NullSink),
+        options,
+    )
+    .unwrap()
+}
+
+fn execute(c: &mut Criterion) {
+    for (group_name, engine) in [
+        ("execute_interpreter", Engine::Interpreter),
+        ("execute_cached", Engine::Cached),
+        ("execute_blocks", Engine::Blocks),
+    ] {
+        let mut group = c.benchmark_group(group_name);
+        group.sample_size(20);
+        for name in ROMS {
+            let rom = rom(name);
+            // The instructions that actually run, which leaves out any the
+            // blocks engine skips.
+            let mut dry_run = vm(rom.clone(), engine);
+            dry_run.start().unwrap();
+            group.throughput(Throughput::Elements(dry_run.instructions()));
+            group.bench_function(name, |b| {
+                b.iter_batched(
+                    || vm(rom.clone(), engine),
+                    |mut vm| vm.start(),
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+fn draw_sprite(c: &mut Criterion) {
+    // Sixteen 5 row sprites, drawn at positions crossing the edges.
+    let sprites: Vec<[u8; 5]> = (0..16u8)
+        .map(|digit| [0xF0 ^ digit, 0x90 | digit, 0xF0, digit << 4, 0x0F ^ digit])
+        .collect();
+    let mut group = c.benchmark_group("draw_sprite");
+    group.throughput(Throughput::Elements(64 * 32));
+    for (name, clip) in [("clip", true), ("wrap", false)] {
+        group.bench_function(name, |b| {
+            let mut screen = Framebuffer::new(64, 32);
+            b.iter(|| {
+                let mut collisions = 0;
+                for y in 0..32 {
+                    for x in 0..64 {
+                        let sprite = &sprites[(x + y) % sprites.len()];
+                        collisions += screen.draw_sprite(x * 3, y * 5, sprite, clip) as u32;
+                    }
+                }
+                collisions
+            })
+        });
+    }
+    group.finish();
+}
+
+fn export(c: &mut Criterion) {
+    let mut vm = vm(rom(ROMS[0]), Engine::default());
+    vm.start().unwrap();
+    let mut screen = Framebuffer::new(64, 32);
+    for y in 0..32 {
+        for x in 0..64 {
+            screen.set_pixel(x, y, (x * 7 + y * 3) % 5 < 2);
+        }
+    }
+    let mut group = c.benchmark_group("export");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("to_bytes", |b| b.iter(|| screen.to_bytes()));
+    group.bench_function("lit_pixels", |b| {
+        b.iter(|| screen.lit_pixels().collect::<Vec<_>>())
+    });
+    group.bench_function("screen_text", |b| b.iter(|| vm.screen_text()));
+    group.finish();
+}
+
+criterion_group!(benches, execute, draw_sprite, export);
+criterion_main!(benches);
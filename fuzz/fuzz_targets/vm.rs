@@ -0,0 +1,93 @@
+//! Runs arbitrary ROMs with arbitrary input for a few frames. Whatever the
+//! ROM does, the VM has to stop with an error rather than panic.
+//!
+//! Run with `cargo +nightly fuzz run vm` from the repository root.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use chip8::{
+    audio::NullSink,
+    font::{Font, FontSet},
+    frontend::{Frontend, MainLoopAction},
+    keypad::Keypad,
+    platform::{Platform, Quirks},
+    rng::RngMode,
+    vm::{Engine, Options, Vm},
+};
+use libfuzzer_sys::fuzz_target;
+
+const MAX_FRAMES: usize = 32;
+const SPEED: u32 = 1000;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    /// Detected from the ROM if not given.
+    platform: Option<Platform>,
+    quirks: Option<Quirks>,
+    engine: Engine,
+    vip_rng: bool,
+    /// A font of arbitrary bytes, or one of the built-in ones.
+    font: Result<Font, FontSet>,
+    /// Where the font goes, only some of which leave room for it.
+    font_address: u16,
+    /// The keys held down in each frame, the run stops after the last one.
+    keypads: Vec<u16>,
+    rom: Vec<u8>,
+}
+
+/// Plays back the keypads, one per frame.
+struct Replay {
+    keypads: Vec<u16>,
+    frame: usize,
+}
+
+impl Frontend for Replay {
+    fn draw(&mut self) -> MainLoopAction {
+        self.frame += 1;
+        if self.frame > self.keypads.len().min(MAX_FRAMES) {
+            MainLoopAction::Interrupt
+        } else {
+            MainLoopAction::Continue
+        }
+    }
+    fn draw_points(&mut self, _points: &[(i32, i32)]) {}
+    fn keypad(&mut self) -> Keypad {
+        let keys = self.keypads[self.frame - 1];
+        Keypad::from_fn(|key| keys >> key & 1 == 1)
+    }
+    fn is_realtime(&self) -> bool {
+        false
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let options = Options {
+        platform: input.platform,
+        quirks: input.quirks,
+        speed: Some(SPEED),
+        seed: Some(0),
+        rng: if input.vip_rng {
+            RngMode::Vip
+        } else {
+            RngMode::Seeded
+        },
+        engine: input.engine,
+        font: match input.font {
+            Ok(font) => font,
+            Err(font_set) => *font_set.font(),
+        },
+        // Mostly below 0x200, where the font may fit.
+        font_address: input.font_address % 0x400,
+        ..Options::default()
+    };
+    let frontend = Replay {
+        keypads: input.keypads,
+        frame: 0,
+    };
+    // Too large for the platform, or no room for the font.
+    let Ok(mut vm) = Vm::new(input.rom, Box::new(frontend), Box::new(NullSink), options) else {
+        return;
+    };
+    let _ = vm.start();
+});
@@ -0,0 +1,265 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Range,
+};
+
+use crate::{platform::Platform, vm::Vm};
+
+#[cfg(test)]
+mod tests;
+
+pub const START_ADDRESS: u16 = 0x200;
+
+/// Addresses of the instructions reachable from the entry point, found by
+/// following jumps, calls and skips. Code only reachable through `BNNN` is
+/// missed, but data is never mistaken for code.
+pub fn trace(rom: &[u8]) -> BTreeSet<u16> {
+    let end = START_ADDRESS as usize + rom.len();
+    let word_at = |address: u16| -> Option<u16> {
+        let offset = address.checked_sub(START_ADDRESS)? as usize;
+        if address as usize + 1 >= end {
+            return None;
+        }
+        Some(((rom[offset] as u16) << 8) | rom[offset + 1] as u16)
+    };
+    let mut code = BTreeSet::new();
+    let mut pending = vec![START_ADDRESS];
+    while let Some(address) = pending.pop() {
+        if code.contains(&address) {
+            continue;
+        }
+        let Some(instruction) = word_at(address) else {
+            continue;
+        };
+        code.insert(address);
+        let next = address.wrapping_add(2);
+        match instruction {
+            0x00EE | 0x00FD => {}
+            0x1260 if address == START_ADDRESS => pending.push(0x2C0),
+            0x1000..=0x1FFF => pending.push(instruction & 0x0FFF),
+            0x2000..=0x2FFF => pending.extend([instruction & 0x0FFF, next]),
+            0xB000..=0xBFFF => {}
+            0xF000 => pending.push(next.wrapping_add(2)),
+            _ if is_skip(instruction) => {
+                // XO-CHIP skips step over the whole of a 4 byte `F000 NNNN`.
+                let skipped = if word_at(next) == Some(0xF000) { 4 } else { 2 };
+                pending.extend([next, next.wrapping_add(skipped)]);
+            }
+            _ => pending.push(next),
+        }
+    }
+    code
+}
+
+/// What `inspect` finds out about a ROM's code.
+pub struct Report {
+    /// How often each kind of instruction appears in the reachable code.
+    pub histogram: BTreeMap<&'static str, usize>,
+    pub call_targets: BTreeSet<u16>,
+    /// Memory drawn as sprites, as far as `I` can be followed.
+    pub sprite_regions: Vec<Range<u16>>,
+    pub self_modifying: SelfModifying,
+    /// Instructions the VM doesn't implement, with the addresses using them.
+    pub unimplemented: BTreeMap<u16, Vec<u16>>,
+}
+
+pub enum SelfModifying {
+    No,
+    /// Stores to memory through an `I` that couldn't be followed.
+    Maybe,
+    /// Stores to memory holding reachable code.
+    Yes,
+}
+
+/// Inspects the reachable code of a ROM meant for `platform`. `I` is followed
+/// through the code in address order rather than execution order, which is
+/// enough for the usual load-then-use sequences.
+pub fn inspect(rom: &[u8], platform: Platform) -> Report {
+    let code = trace(rom);
+    let word_at = |address: u16| {
+        let offset = (address - START_ADDRESS) as usize;
+        match rom.get(offset..offset + 2) {
+            Some(&[high, low]) => ((high as u16) << 8) | low as u16,
+            _ => 0,
+        }
+    };
+    let writes_code = |range: Range<u16>| {
+        code.iter()
+            .any(|&address| address < range.end && address + 2 > range.start)
+    };
+    let mut report = Report {
+        histogram: BTreeMap::new(),
+        call_targets: BTreeSet::new(),
+        sprite_regions: vec![],
+        self_modifying: SelfModifying::No,
+        unimplemented: BTreeMap::new(),
+    };
+    let mut i_reg = None;
+    for &address in &code {
+        let instruction = word_at(address);
+        *report.histogram.entry(pattern(instruction)).or_default() += 1;
+        if !Vm::is_implemented(instruction, platform) {
+            report
+                .unimplemented
+                .entry(instruction)
+                .or_default()
+                .push(address);
+        }
+        let x = instruction >> 8 & 0xF;
+        let y = instruction >> 4 & 0xF;
+        let stored = match instruction & 0xF0FF {
+            0xF033 => Some(3),
+            0xF055 => Some(x + 1),
+            _ if instruction & 0xF00F == 0x5002 => Some(x.abs_diff(y) + 1),
+            _ => None,
+        };
+        if let Some(len) = stored {
+            match i_reg {
+                Some(i) if writes_code(i..i.saturating_add(len)) => {
+                    report.self_modifying = SelfModifying::Yes
+                }
+                None if !matches!(report.self_modifying, SelfModifying::Yes) => {
+                    report.self_modifying = SelfModifying::Maybe
+                }
+                _ => {}
+            }
+        }
+        match instruction {
+            0x2000..=0x2FFF => {
+                report.call_targets.insert(instruction & 0xFFF);
+            }
+            0xA000..=0xAFFF => i_reg = Some(instruction & 0xFFF),
+            0xF000 => i_reg = Some(word_at(address + 2)),
+            0xD000..=0xDFFF => {
+                if let Some(i) = i_reg {
+                    // `DXY0` draws a 16x16 sprite on SUPER-CHIP.
+                    let height = match instruction & 0xF {
+                        0 => 32,
+                        n => n,
+                    };
+                    report.sprite_regions.push(i..i.saturating_add(height));
+                }
+            }
+            _ if matches!(instruction & 0xF0FF, 0xF01E | 0xF029 | 0xF030) => i_reg = None,
+            _ if matches!(instruction & 0xF0FF, 0xF055 | 0xF065) => i_reg = None,
+            _ => {}
+        }
+    }
+    report.sprite_regions.sort_by_key(|region| region.start);
+    report.sprite_regions.dedup_by(|next, region| {
+        if next.start > region.end {
+            return false;
+        }
+        region.end = region.end.max(next.end);
+        true
+    });
+    report
+}
+
+/// The instruction's opcode with its operands as letters, e.g. `DXYN`.
+pub fn pattern(instruction: u16) -> &'static str {
+    match instruction {
+        0x00E0 => "00E0",
+        0x00EE => "00EE",
+        0x00C0..=0x00CF => "00CN",
+        0x00D0..=0x00DF => "00DN",
+        0x00FB => "00FB",
+        0x00FC => "00FC",
+        0x00FD => "00FD",
+        0x00FE => "00FE",
+        0x00FF => "00FF",
+        0x0000..=0x0FFF => "0NNN",
+        0x1000..=0x1FFF => "1NNN",
+        0x2000..=0x2FFF => "2NNN",
+        0x3000..=0x3FFF => "3XNN",
+        0x4000..=0x4FFF => "4XNN",
+        0x6000..=0x6FFF => "6XNN",
+        0x7000..=0x7FFF => "7XNN",
+        0xA000..=0xAFFF => "ANNN",
+        0xB000..=0xBFFF => "BNNN",
+        0xC000..=0xCFFF => "CXNN",
+        0xD000..=0xDFFF => "DXYN",
+        0xF000 => "F000",
+        0xF002 => "F002",
+        _ => match instruction & 0xF00F {
+            0x5000 => "5XY0",
+            0x5002 => "5XY2",
+            0x5003 => "5XY3",
+            0x8000 => "8XY0",
+            0x8001 => "8XY1",
+            0x8002 => "8XY2",
+            0x8003 => "8XY3",
+            0x8004 => "8XY4",
+            0x8005 => "8XY5",
+            0x8006 => "8XY6",
+            0x8007 => "8XY7",
+            0x800E => "8XYE",
+            0x9000 => "9XY0",
+            _ => match instruction & 0xF0FF {
+                0xE09E => "EX9E",
+                0xE0A1 => "EXA1",
+                0xF001 => "FN01",
+                0xF007 => "FX07",
+                0xF00A => "FX0A",
+                0xF015 => "FX15",
+                0xF018 => "FX18",
+                0xF01E => "FX1E",
+                0xF029 => "FX29",
+                0xF030 => "FX30",
+                0xF033 => "FX33",
+                0xF03A => "FX3A",
+                0xF055 => "FX55",
+                0xF065 => "FX65",
+                0xF075 => "FX75",
+                0xF085 => "FX85",
+                _ => "invalid",
+            },
+        },
+    }
+}
+
+/// Guesses the platform a ROM targets from the instructions it can reach.
+/// ROMs using nothing newer than CHIP-8's own instructions, including `0NNN`
+/// calls to VIP machine code, are taken to be for the COSMAC VIP, so they get
+/// the original interpreter's quirks.
+pub fn detect_platform(rom: &[u8]) -> Platform {
+    if rom.starts_with(&[0x12, 0x60]) {
+        return Platform::VipHires;
+    }
+    let instructions: Vec<u16> = trace(rom)
+        .into_iter()
+        .map(|address| {
+            let offset = (address - START_ADDRESS) as usize;
+            ((rom[offset] as u16) << 8) | rom[offset + 1] as u16
+        })
+        .collect();
+    if instructions.iter().any(|&i| is_xo_chip_only(i)) {
+        Platform::XoChip
+    } else if instructions.iter().any(|&i| is_super_chip_only(i)) {
+        Platform::SuperChip
+    } else {
+        Platform::Vip
+    }
+}
+
+fn is_skip(instruction: u16) -> bool {
+    matches!(instruction & 0xF000, 0x3000 | 0x4000)
+        || matches!(instruction & 0xF00F, 0x5000 | 0x9000)
+        || matches!(instruction & 0xF0FF, 0xE09E | 0xE0A1)
+}
+
+fn is_super_chip_only(instruction: u16) -> bool {
+    matches!(instruction, 0x00FB..=0x00FF)
+        || instruction & 0xFFF0 == 0x00C0
+        || instruction & 0xF00F == 0xD000
+        || matches!(instruction & 0xF0FF, 0xF030 | 0xF075 | 0xF085)
+}
+
+fn is_xo_chip_only(instruction: u16) -> bool {
+    instruction == 0xF000
+        || instruction == 0xF002
+        || instruction & 0xFFF0 == 0x00D0
+        || matches!(instruction & 0xF00F, 0x5002 | 0x5003)
+        || instruction & 0xF0FF == 0xF001
+        || instruction & 0xF0FF == 0xF03A
+}
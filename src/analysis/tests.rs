@@ -0,0 +1,105 @@
+use super::*;
+
+fn rom(program: &[u16]) -> Vec<u8> {
+    program.iter().flat_map(|i| i.to_be_bytes()).collect()
+}
+
+#[test]
+fn detects_vip_hires_from_its_entry_jump() {
+    assert_eq!(detect_platform(&rom(&[0x1260])), Platform::VipHires);
+}
+
+#[test]
+fn detects_xo_chip() {
+    // LD I, long 0x0300, then loops.
+    let program = rom(&[0xF000, 0x0300, 0x1204]);
+    assert_eq!(detect_platform(&program), Platform::XoChip);
+    // SAVE V1-V2.
+    assert_eq!(detect_platform(&rom(&[0x5122, 0x1202])), Platform::XoChip);
+}
+
+#[test]
+fn detects_super_chip() {
+    // HIGH, then loops.
+    assert_eq!(
+        detect_platform(&rom(&[0x00FF, 0x1202])),
+        Platform::SuperChip
+    );
+    // A 16x16 sprite.
+    assert_eq!(
+        detect_platform(&rom(&[0xD120, 0x1202])),
+        Platform::SuperChip
+    );
+}
+
+#[test]
+fn detects_machine_code_calls_as_vip() {
+    assert_eq!(detect_platform(&rom(&[0x0123, 0x1202])), Platform::Vip);
+}
+
+#[test]
+fn falls_back_to_vip() {
+    let program = rom(&[0x00E0, 0x6001, 0x8016, 0xB200, 0x1208]);
+    assert_eq!(detect_platform(&program), Platform::Vip);
+}
+
+#[test]
+fn ignores_opcodes_in_unreachable_data() {
+    // Jumps over what would be SUPER-CHIP's HIGH and XO-CHIP's F000.
+    let program = rom(&[0x1206, 0x00FF, 0xF000, 0x1206]);
+    assert_eq!(detect_platform(&program), Platform::Vip);
+}
+
+#[test]
+fn inspects_the_ibm_logo() {
+    let rom = include_bytes!("../../examples/IBM Logo.ch8");
+    let report = inspect(rom, Platform::Chip48);
+    let histogram: Vec<(&str, usize)> = report.histogram.into_iter().collect();
+    assert_eq!(
+        histogram,
+        [
+            ("00E0", 1),
+            ("1NNN", 1),
+            ("6XNN", 2),
+            ("7XNN", 5),
+            ("ANNN", 6),
+            ("DXYN", 6)
+        ]
+    );
+    assert!(report.call_targets.is_empty());
+    // The six 15 byte letters, back to back.
+    assert_eq!(report.sprite_regions, vec![0x22A..0x284]);
+    assert!(matches!(report.self_modifying, SelfModifying::No));
+    assert!(report.unimplemented.is_empty());
+}
+
+#[test]
+fn inspects_calls_and_stores_into_code() {
+    let program = rom(&[
+        0x2206, // 200: CALL 0x206
+        0x1202, // 202: JP 0x202
+        0x00FF, // 204: never reached
+        0xA200, // 206: LD I, 0x200
+        0xF055, // 208: LD [I], V0
+        0xF000, // 20A: LD I, long 0x300
+        0x0300, //
+        0x00EE, // 20E: RET
+    ]);
+    let report = inspect(&program, Platform::Vip);
+    assert_eq!(report.call_targets.into_iter().collect::<Vec<_>>(), [0x206]);
+    assert!(matches!(report.self_modifying, SelfModifying::Yes));
+    let unimplemented: Vec<(u16, Vec<u16>)> = report.unimplemented.into_iter().collect();
+    assert_eq!(unimplemented, [(0xF000, vec![0x20A])]);
+    // XO-CHIP has it.
+    assert!(inspect(&program, Platform::XoChip).unimplemented.is_empty());
+}
+
+#[test]
+fn stores_through_an_untracked_i_may_modify_code() {
+    // LD I, 0x300; ADD I, V0; LD [I], V0; then loops.
+    let report = inspect(&rom(&[0xA300, 0xF01E, 0xF055, 0x1206]), Platform::Vip);
+    assert!(matches!(report.self_modifying, SelfModifying::Maybe));
+    // Stores past the code don't.
+    let report = inspect(&rom(&[0xA300, 0xF055, 0x1204]), Platform::Vip);
+    assert!(matches!(report.self_modifying, SelfModifying::No));
+}
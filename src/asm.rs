@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use crate::analysis::START_ADDRESS;
+
+#[cfg(test)]
+mod tests;
+
+const MNEMONICS: [&str; 32] = [
+    "cls", "ret", "scd", "scu", "scr", "scl", "exit", "low", "high", "sys", "jp", "call", "se",
+    "sne", "save", "load", "ld", "add", "or", "and", "xor", "sub", "shr", "subn", "shl", "rnd",
+    "drw", "skp", "sknp", "plane", "audio", "pitch",
+];
+
+/// Assembles the syntax `disasm::disassemble` writes: one instruction or
+/// `DB`/`DW` directive per line, `label:` definitions and `;` comments.
+/// Numbers can be decimal, `0x` hex or `0b` binary.
+pub fn assemble(source: &str) -> Result<Vec<u8>, String> {
+    let mut lines = vec![];
+    let mut labels = HashMap::new();
+    let mut address = START_ADDRESS as usize;
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let mut line = line.split(';').next().unwrap().trim();
+        if let Some((name, rest)) = line.split_once(':') {
+            let name = name.trim();
+            if !is_identifier(name) {
+                return Err(format!("line {line_number}: invalid label `{name}`"));
+            }
+            if labels.insert(name.to_lowercase(), address).is_some() {
+                return Err(format!("line {line_number}: label `{name}` defined twice"));
+            }
+            line = rest.trim();
+        }
+        if line.is_empty() {
+            continue;
+        }
+        let (mnemonic, operands) = match line.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (
+                mnemonic.to_lowercase(),
+                operands.split(',').map(str::trim).collect(),
+            ),
+            None => (line.to_lowercase(), vec![]),
+        };
+        address += match mnemonic.as_str() {
+            "db" => operands.len(),
+            "dw" => operands.len() * 2,
+            "ld" if operands.last().is_some_and(|o| is_long(o)) => 4,
+            _ => 2,
+        };
+        lines.push((line_number, mnemonic, operands));
+    }
+    let mut rom = vec![];
+    for (line_number, mnemonic, operands) in lines {
+        let bytes = encode(&mnemonic, &operands, &labels)
+            .map_err(|e| format!("line {line_number}: {e}"))?;
+        rom.extend(bytes);
+    }
+    Ok(rom)
+}
+
+enum Operand {
+    Register(u16),
+    I,
+    IndirectI,
+    Delay,
+    Sound,
+    Key,
+    Font,
+    HiresFont,
+    Bcd,
+    Flags,
+    Long(u32),
+    Value(u32),
+}
+
+fn encode(
+    mnemonic: &str,
+    operands: &[&str],
+    labels: &HashMap<String, usize>,
+) -> Result<Vec<u8>, String> {
+    use Operand::*;
+
+    match mnemonic {
+        "db" => {
+            return operands
+                .iter()
+                .map(|o| Ok(check(value(o, labels)?, 0xFF, "byte")? as u8))
+                .collect()
+        }
+        "dw" => {
+            let mut bytes = vec![];
+            for o in operands {
+                bytes.extend(check(value(o, labels)?, 0xFFFF, "word")?.to_be_bytes());
+            }
+            return Ok(bytes);
+        }
+        _ => {}
+    }
+    let operands = operands
+        .iter()
+        .map(|o| operand(o, labels))
+        .collect::<Result<Vec<Operand>, String>>()?;
+    let address = |a: u32| check(a, 0xFFF, "address");
+    let byte = |b: u32| check(b, 0xFF, "byte");
+    let nibble = |n: u32| check(n, 0xF, "nibble");
+    let instruction = match (mnemonic, &operands[..]) {
+        ("cls", []) => 0x00E0,
+        ("ret", []) => 0x00EE,
+        ("scd", [Value(n)]) => 0x00C0 | nibble(*n)?,
+        ("scu", [Value(n)]) => 0x00D0 | nibble(*n)?,
+        ("scr", []) => 0x00FB,
+        ("scl", []) => 0x00FC,
+        ("exit", []) => 0x00FD,
+        ("low", []) => 0x00FE,
+        ("high", []) => 0x00FF,
+        ("sys", [Value(a)]) => address(*a)?,
+        ("jp", [Value(a)]) => 0x1000 | address(*a)?,
+        ("call", [Value(a)]) => 0x2000 | address(*a)?,
+        ("se", [Register(x), Value(b)]) => 0x3000 | x << 8 | byte(*b)?,
+        ("sne", [Register(x), Value(b)]) => 0x4000 | x << 8 | byte(*b)?,
+        ("se", [Register(x), Register(y)]) => 0x5000 | x << 8 | y << 4,
+        ("save", [Register(x), Register(y)]) => 0x5002 | x << 8 | y << 4,
+        ("load", [Register(x), Register(y)]) => 0x5003 | x << 8 | y << 4,
+        ("ld", [Register(x), Value(b)]) => 0x6000 | x << 8 | byte(*b)?,
+        ("add", [Register(x), Value(b)]) => 0x7000 | x << 8 | byte(*b)?,
+        ("ld", [Register(x), Register(y)]) => 0x8000 | x << 8 | y << 4,
+        ("or", [Register(x), Register(y)]) => 0x8001 | x << 8 | y << 4,
+        ("and", [Register(x), Register(y)]) => 0x8002 | x << 8 | y << 4,
+        ("xor", [Register(x), Register(y)]) => 0x8003 | x << 8 | y << 4,
+        ("add", [Register(x), Register(y)]) => 0x8004 | x << 8 | y << 4,
+        ("sub", [Register(x), Register(y)]) => 0x8005 | x << 8 | y << 4,
+        ("shr", [Register(x)]) => 0x8006 | x << 8 | x << 4,
+        ("shr", [Register(x), Register(y)]) => 0x8006 | x << 8 | y << 4,
+        ("subn", [Register(x), Register(y)]) => 0x8007 | x << 8 | y << 4,
+        ("shl", [Register(x)]) => 0x800E | x << 8 | x << 4,
+        ("shl", [Register(x), Register(y)]) => 0x800E | x << 8 | y << 4,
+        ("sne", [Register(x), Register(y)]) => 0x9000 | x << 8 | y << 4,
+        ("ld", [I, Value(a)]) => 0xA000 | address(*a)?,
+        ("ld", [I, Long(a)]) => {
+            let a = check(*a, 0xFFFF, "address")?;
+            return Ok(vec![0xF0, 0x00, (a >> 8) as u8, a as u8]);
+        }
+        ("jp", [Register(0), Value(a)]) => 0xB000 | address(*a)?,
+        ("rnd", [Register(x), Value(b)]) => 0xC000 | x << 8 | byte(*b)?,
+        ("drw", [Register(x), Register(y), Value(n)]) => 0xD000 | x << 8 | y << 4 | nibble(*n)?,
+        ("skp", [Register(x)]) => 0xE09E | x << 8,
+        ("sknp", [Register(x)]) => 0xE0A1 | x << 8,
+        ("plane", [Value(n)]) => 0xF001 | nibble(*n)? << 8,
+        ("audio", []) => 0xF002,
+        ("ld", [Register(x), Delay]) => 0xF007 | x << 8,
+        ("ld", [Register(x), Key]) => 0xF00A | x << 8,
+        ("ld", [Delay, Register(x)]) => 0xF015 | x << 8,
+        ("ld", [Sound, Register(x)]) => 0xF018 | x << 8,
+        ("add", [I, Register(x)]) => 0xF01E | x << 8,
+        ("ld", [Font, Register(x)]) => 0xF029 | x << 8,
+        ("ld", [HiresFont, Register(x)]) => 0xF030 | x << 8,
+        ("ld", [Bcd, Register(x)]) => 0xF033 | x << 8,
+        ("pitch", [Register(x)]) => 0xF03A | x << 8,
+        ("ld", [IndirectI, Register(x)]) => 0xF055 | x << 8,
+        ("ld", [Register(x), IndirectI]) => 0xF065 | x << 8,
+        ("ld", [Flags, Register(x)]) => 0xF075 | x << 8,
+        ("ld", [Register(x), Flags]) => 0xF085 | x << 8,
+        _ if MNEMONICS.contains(&mnemonic) => {
+            return Err(format!(
+                "invalid operands for `{}`",
+                mnemonic.to_uppercase()
+            ))
+        }
+        _ => return Err(format!("unknown instruction `{mnemonic}`")),
+    };
+    Ok(instruction.to_be_bytes().to_vec())
+}
+
+fn operand(text: &str, labels: &HashMap<String, usize>) -> Result<Operand, String> {
+    let lower = text.to_lowercase();
+    Ok(match lower.as_str() {
+        "i" => Operand::I,
+        "[i]" => Operand::IndirectI,
+        "dt" => Operand::Delay,
+        "st" => Operand::Sound,
+        "k" => Operand::Key,
+        "f" => Operand::Font,
+        "hf" => Operand::HiresFont,
+        "b" => Operand::Bcd,
+        "r" => Operand::Flags,
+        _ => {
+            if is_long(&lower) {
+                let (_, rest) = lower.split_once(char::is_whitespace).unwrap();
+                return Ok(Operand::Long(value(rest.trim(), labels)?));
+            }
+            match lower.strip_prefix('v') {
+                Some(register) if register.len() == 1 => match u16::from_str_radix(register, 16) {
+                    Ok(register) => Operand::Register(register),
+                    Err(_) => Operand::Value(value(text, labels)?),
+                },
+                _ => Operand::Value(value(text, labels)?),
+            }
+        }
+    })
+}
+
+fn value(text: &str, labels: &HashMap<String, usize>) -> Result<u32, String> {
+    let lower = text.to_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        u32::from_str_radix(binary, 2)
+    } else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+        lower.parse()
+    } else {
+        return match labels.get(&lower) {
+            Some(&address) => Ok(address as u32),
+            None if text.is_empty() => Err("missing operand".to_string()),
+            None => Err(format!("unknown label `{text}`")),
+        };
+    };
+    parsed.map_err(|_| format!("invalid number `{text}`"))
+}
+
+fn check(value: u32, max: u32, what: &str) -> Result<u16, String> {
+    if value > max {
+        return Err(format!(
+            "{what} {value:#X} is out of range, at most {max:#X}"
+        ));
+    }
+    Ok(value as u16)
+}
+
+fn is_long(operand: &str) -> bool {
+    matches!(
+        operand.split_once(char::is_whitespace),
+        Some((long, _)) if long.eq_ignore_ascii_case("long")
+    )
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
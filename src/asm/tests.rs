@@ -0,0 +1,51 @@
+use super::*;
+
+#[test]
+fn assembles_labels_and_directives() {
+    let source = "
+    start:
+        LD V0, 0x0A ; a comment
+        JP start
+        DB 1, 0b10
+        DW 0x1234
+    ";
+    assert_eq!(
+        assemble(source),
+        Ok(vec![0x60, 0x0A, 0x12, 0x00, 0x01, 0x02, 0x12, 0x34])
+    );
+}
+
+#[test]
+fn unknown_mnemonics_fail() {
+    assert_eq!(
+        assemble("CLS\nFOO V0"),
+        Err("line 2: unknown instruction `foo`".to_string())
+    );
+}
+
+#[test]
+fn out_of_range_operands_fail() {
+    let error = assemble("LD V0, 256").unwrap_err();
+    assert!(error.starts_with("line 1: "), "{error}");
+    assert!(error.contains("out of range"), "{error}");
+    let error = assemble("JP 0x1000").unwrap_err();
+    assert!(error.contains("out of range"), "{error}");
+}
+
+#[test]
+fn undefined_labels_fail() {
+    assert_eq!(
+        assemble("JP nowhere"),
+        Err("line 1: unknown label `nowhere`".to_string())
+    );
+}
+
+#[test]
+fn bad_operands_and_labels_fail() {
+    assert_eq!(
+        assemble("CLS V0"),
+        Err("line 1: invalid operands for `CLS`".to_string())
+    );
+    assert!(assemble("a:\na:").unwrap_err().contains("defined twice"));
+    assert!(assemble("1a: CLS").unwrap_err().contains("invalid label"));
+}
@@ -1,24 +1,414 @@
-use std::{fs::File, io::BufReader};
+use std::{
+    f32::consts::PI,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{
+        atomic::{fence, AtomicBool, AtomicU32, AtomicU64, AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Source};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use rodio::{OutputStream, Sink, Source};
 
-pub struct Audio {
-    stream_handle: OutputStreamHandle,
+#[cfg(test)]
+mod tests;
+
+const SAMPLE_RATE: u32 = 44_100;
+// Fade in/out over ~5ms so starting and stopping the tone doesn't click.
+const RAMP_STEP: f32 = 1.0 / (SAMPLE_RATE as f32 * 0.005);
+// XO-CHIP pitch 64 plays the pattern at 4000 bits per second.
+const DEFAULT_PITCH: u8 = 64;
+const PATTERN_BITS: usize = 128;
+pub const DEFAULT_FREQUENCY: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.25;
+
+#[derive(Clone, Copy)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Waveform, String> {
+        match s {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            _ => Err(format!(
+                "unknown waveform `{s}`, expected square, sine or triangle"
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct AudioConfig {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    pub volume: f32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> AudioConfig {
+        AudioConfig {
+            waveform: Waveform::Square,
+            frequency: DEFAULT_FREQUENCY,
+            volume: DEFAULT_VOLUME,
+        }
+    }
+}
+
+/// Where the tone driven by the sound timer ends up.
+pub trait AudioSink {
+    fn set_playing(&mut self, playing: bool);
+    /// Replaces the generated tone with an XO-CHIP 1-bit pattern (`F002`).
+    fn set_pattern(&mut self, pattern: [u8; 16]);
+    /// Sets the XO-CHIP pattern playback rate (`FX3A`).
+    fn set_pitch(&mut self, pitch: u8);
+    /// Stops the tone and goes back to the generated one at the default
+    /// pitch, as when the machine is switched on.
+    fn reset(&mut self);
+    /// Called once per 60 Hz timer tick, after a frame of emulated time.
+    fn end_frame(&mut self) {}
 }
 
-impl Audio {
-    pub fn new() -> Audio {
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        Audio { stream_handle }
+#[derive(Clone)]
+pub enum AudioBackend {
+    /// rodio if an output device is available, silence otherwise.
+    Auto,
+    Rodio,
+    Null,
+    Wav(PathBuf),
+}
+
+impl FromStr for AudioBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AudioBackend, String> {
+        match s {
+            "auto" => Ok(AudioBackend::Auto),
+            "rodio" => Ok(AudioBackend::Rodio),
+            "null" => Ok(AudioBackend::Null),
+            s => match s.strip_prefix("wav:") {
+                Some(path) if !path.is_empty() => Ok(AudioBackend::Wav(PathBuf::from(path))),
+                _ => Err(format!(
+                    "unknown audio backend `{s}`, expected auto, rodio, null or wav:<file>"
+                )),
+            },
+        }
     }
-    pub fn play(&self) {
-        let file = BufReader::new(File::open("beep.wav").unwrap());
-        let source = Decoder::new(file).unwrap();
-        match self.stream_handle.play_raw(source.convert_samples()) {
-            Ok(_) => {}
+}
+
+pub fn open_sink(
+    backend: &AudioBackend,
+    config: AudioConfig,
+) -> Result<Box<dyn AudioSink>, String> {
+    match backend {
+        AudioBackend::Auto => match RodioSink::new(config) {
+            Ok(sink) => Ok(Box::new(sink)),
             Err(e) => {
-                println!("Error: {}", e);
+                eprintln!("No audio output ({e}), running silently");
+                Ok(Box::new(NullSink))
+            }
+        },
+        AudioBackend::Rodio => Ok(Box::new(RodioSink::new(config)?)),
+        AudioBackend::Null => Ok(Box::new(NullSink)),
+        AudioBackend::Wav(path) => Ok(Box::new(WavSink::new(path, config)?)),
+    }
+}
+
+pub struct RodioSink {
+    _stream: OutputStream,
+    _sink: Sink,
+    state: Arc<ToneState>,
+}
+
+impl RodioSink {
+    pub fn new(config: AudioConfig) -> Result<RodioSink, String> {
+        let (stream, stream_handle) = OutputStream::try_default().map_err(|e| e.to_string())?;
+        let sink = Sink::try_new(&stream_handle).map_err(|e| e.to_string())?;
+        let state = Arc::new(ToneState::default());
+        sink.append(Tone::new(config, Arc::clone(&state)));
+        Ok(RodioSink {
+            _stream: stream,
+            _sink: sink,
+            state,
+        })
+    }
+}
+
+impl AudioSink for RodioSink {
+    fn set_playing(&mut self, playing: bool) {
+        self.state.playing.store(playing, Ordering::Relaxed);
+    }
+    fn set_pattern(&mut self, pattern: [u8; 16]) {
+        self.state.set_pattern(Some(pattern));
+    }
+    fn set_pitch(&mut self, pitch: u8) {
+        self.state.pitch.store(pitch, Ordering::Relaxed);
+    }
+    fn reset(&mut self) {
+        self.state.reset();
+    }
+}
+
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn set_playing(&mut self, _playing: bool) {}
+    fn set_pattern(&mut self, _pattern: [u8; 16]) {}
+    fn set_pitch(&mut self, _pitch: u8) {}
+    fn reset(&mut self) {}
+}
+
+/// Sends the tone to several sinks, e.g. speakers and a WAV file.
+pub struct TeeSink(pub Vec<Box<dyn AudioSink>>);
+
+impl AudioSink for TeeSink {
+    fn set_playing(&mut self, playing: bool) {
+        self.0.iter_mut().for_each(|sink| sink.set_playing(playing));
+    }
+    fn set_pattern(&mut self, pattern: [u8; 16]) {
+        self.0.iter_mut().for_each(|sink| sink.set_pattern(pattern));
+    }
+    fn set_pitch(&mut self, pitch: u8) {
+        self.0.iter_mut().for_each(|sink| sink.set_pitch(pitch));
+    }
+    fn reset(&mut self) {
+        self.0.iter_mut().for_each(|sink| sink.reset());
+    }
+    fn end_frame(&mut self) {
+        self.0.iter_mut().for_each(|sink| sink.end_frame());
+    }
+}
+
+/// Renders the tone into a WAV file, one 60th of a second per timer tick, so
+/// the recording follows emulated time rather than wall-clock time.
+pub struct WavSink {
+    tone: Tone,
+    writer: WavWriter<BufWriter<File>>,
+}
+
+impl WavSink {
+    pub fn new(path: &Path, config: AudioConfig) -> Result<WavSink, String> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let writer = WavWriter::create(path, spec)
+            .map_err(|e| format!("could not create {}: {e}", path.display()))?;
+        Ok(WavSink {
+            tone: Tone::new(config, Arc::new(ToneState::default())),
+            writer,
+        })
+    }
+}
+
+impl AudioSink for WavSink {
+    fn set_playing(&mut self, playing: bool) {
+        self.tone.state.playing.store(playing, Ordering::Relaxed);
+    }
+    fn set_pattern(&mut self, pattern: [u8; 16]) {
+        self.tone.state.set_pattern(Some(pattern));
+    }
+    fn set_pitch(&mut self, pitch: u8) {
+        self.tone.state.pitch.store(pitch, Ordering::Relaxed);
+    }
+    fn reset(&mut self) {
+        self.tone.state.reset();
+    }
+    fn end_frame(&mut self) {
+        for _ in 0..SAMPLE_RATE / 60 {
+            let sample = self.tone.next().unwrap_or(0.0);
+            if let Err(e) = self.writer.write_sample((sample * i16::MAX as f32) as i16) {
+                eprintln!("Error writing audio: {e}");
+                return;
             }
         }
     }
 }
+
+/// What the emulator tells the tone, shared with the audio callback without
+/// locking.
+struct ToneState {
+    playing: AtomicBool,
+    /// The pattern, its two halves and whether there is one, written as a
+    /// seqlock: the generation is odd while they are being written and
+    /// bumped to the next even value when done.
+    pattern: [AtomicU64; 2],
+    has_pattern: AtomicBool,
+    pattern_generation: AtomicU32,
+    pitch: AtomicU8,
+}
+
+impl Default for ToneState {
+    fn default() -> ToneState {
+        ToneState {
+            playing: AtomicBool::new(false),
+            pattern: [AtomicU64::new(0), AtomicU64::new(0)],
+            has_pattern: AtomicBool::new(false),
+            pattern_generation: AtomicU32::new(0),
+            pitch: AtomicU8::new(DEFAULT_PITCH),
+        }
+    }
+}
+
+impl ToneState {
+    /// Only ever called from one thread, the emulator's.
+    fn set_pattern(&self, pattern: Option<[u8; 16]>) {
+        let generation = self.pattern_generation.load(Ordering::Relaxed);
+        self.pattern_generation
+            .store(generation.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        let bytes = pattern.unwrap_or_default();
+        for (half, bytes) in self.pattern.iter().zip(bytes.chunks_exact(8)) {
+            half.store(
+                u64::from_be_bytes(bytes.try_into().unwrap()),
+                Ordering::Relaxed,
+            );
+        }
+        self.has_pattern.store(pattern.is_some(), Ordering::Relaxed);
+        self.pattern_generation
+            .store(generation.wrapping_add(2), Ordering::Release);
+    }
+
+    /// The pattern and its generation if it has changed since `generation`
+    /// and isn't being written.
+    fn pattern_since(&self, generation: u32) -> Option<(u32, Option<[u8; 16]>)> {
+        let before = self.pattern_generation.load(Ordering::Acquire);
+        if before == generation || before % 2 == 1 {
+            return None;
+        }
+        let mut pattern = [0; 16];
+        for (half, bytes) in self.pattern.iter().zip(pattern.chunks_exact_mut(8)) {
+            bytes.copy_from_slice(&half.load(Ordering::Relaxed).to_be_bytes());
+        }
+        let has_pattern = self.has_pattern.load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        let after = self.pattern_generation.load(Ordering::Relaxed);
+        (before == after).then_some((before, has_pattern.then_some(pattern)))
+    }
+
+    fn reset(&self) {
+        self.playing.store(false, Ordering::Relaxed);
+        self.set_pattern(None);
+        self.pitch.store(DEFAULT_PITCH, Ordering::Relaxed);
+    }
+}
+
+struct Tone {
+    config: AudioConfig,
+    state: Arc<ToneState>,
+    /// The last pattern read from `state`, and its generation.
+    pattern: Option<[u8; 16]>,
+    pattern_generation: u32,
+    phase: f32,
+    pattern_position: f64,
+    gain: f32,
+}
+
+impl Tone {
+    fn new(config: AudioConfig, state: Arc<ToneState>) -> Tone {
+        Tone {
+            config,
+            state,
+            pattern: None,
+            pattern_generation: 0,
+            phase: 0.0,
+            pattern_position: 0.0,
+            gain: 0.0,
+        }
+    }
+    fn waveform_sample(&mut self) -> f32 {
+        let sample = match self.config.waveform {
+            Waveform::Square => {
+                if self.phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (self.phase * 2.0 * PI).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+        };
+        self.phase = (self.phase + self.config.frequency / SAMPLE_RATE as f32) % 1.0;
+        sample
+    }
+    /// Resamples the 128-bit pattern to `SAMPLE_RATE` by averaging the bits
+    /// each output sample spans, so high pitches don't alias. rodio then
+    /// converts `SAMPLE_RATE` to whatever rate the output device runs at.
+    fn pattern_sample(&mut self, pattern: &[u8; 16]) -> f32 {
+        let pitch = self.state.pitch.load(Ordering::Relaxed) as f64;
+        let bit_rate = 4000.0 * 2f64.powf((pitch - 64.0) / 48.0);
+        let step = bit_rate / SAMPLE_RATE as f64;
+        let mut remaining = step;
+        let mut sum = 0.0;
+        while remaining > 0.0 {
+            // Rounding can leave the position a hair short of a bit it
+            // has reached, so wrap before indexing.
+            self.pattern_position %= PATTERN_BITS as f64;
+            let bit = self.pattern_position as usize;
+            let until_next_bit = (bit + 1) as f64 - self.pattern_position;
+            let span = remaining.min(until_next_bit);
+            let level = if (pattern[bit / 8] >> (7 - bit % 8)) & 1 == 1 {
+                1.0
+            } else {
+                -1.0
+            };
+            sum += level * span;
+            remaining -= span;
+            if span == until_next_bit {
+                self.pattern_position = ((bit + 1) % PATTERN_BITS) as f64;
+            } else {
+                self.pattern_position += span;
+            }
+        }
+        (sum / step) as f32
+    }
+}
+
+impl Iterator for Tone {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let target = if self.state.playing.load(Ordering::Relaxed) {
+            1.0
+        } else {
+            0.0
+        };
+        self.gain += (target - self.gain).clamp(-RAMP_STEP, RAMP_STEP);
+        if let Some((generation, pattern)) = self.state.pattern_since(self.pattern_generation) {
+            self.pattern_generation = generation;
+            self.pattern = pattern;
+        }
+        let sample = match self.pattern {
+            Some(pattern) => self.pattern_sample(&pattern),
+            None => self.waveform_sample(),
+        };
+        Some(sample * self.gain * self.config.volume)
+    }
+}
+
+impl Source for Tone {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        1
+    }
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
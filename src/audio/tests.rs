@@ -0,0 +1,59 @@
+use super::*;
+
+fn tone() -> Tone {
+    Tone::new(AudioConfig::default(), Arc::new(ToneState::default()))
+}
+
+#[test]
+fn pattern_reaches_the_tone() {
+    let mut tone = tone();
+    tone.state.playing.store(true, Ordering::Relaxed);
+    tone.next();
+    assert_eq!(tone.pattern, None);
+    tone.state.set_pattern(Some([0xFF; 16]));
+    tone.next();
+    assert_eq!(tone.pattern, Some([0xFF; 16]));
+    tone.state.set_pattern(Some([0x0F; 16]));
+    tone.next();
+    assert_eq!(tone.pattern, Some([0x0F; 16]));
+}
+
+#[test]
+fn pattern_is_only_read_once_per_change() {
+    let state = ToneState::default();
+    assert_eq!(state.pattern_since(0), None);
+    state.set_pattern(Some([1; 16]));
+    let (generation, pattern) = state.pattern_since(0).unwrap();
+    assert_eq!(pattern, Some([1; 16]));
+    assert_eq!(state.pattern_since(generation), None);
+}
+
+#[test]
+fn reset_goes_back_to_the_generated_tone() {
+    let mut tone = tone();
+    tone.state.set_pattern(Some([0xFF; 16]));
+    tone.state.pitch.store(200, Ordering::Relaxed);
+    tone.state.playing.store(true, Ordering::Relaxed);
+    tone.next();
+    assert!(tone.pattern.is_some());
+    tone.state.reset();
+    tone.next();
+    assert_eq!(tone.pattern, None);
+    assert_eq!(tone.state.pitch.load(Ordering::Relaxed), DEFAULT_PITCH);
+    assert!(!tone.state.playing.load(Ordering::Relaxed));
+}
+
+#[test]
+fn pattern_position_wraps_at_the_end() {
+    let mut tone = tone();
+    tone.state.pitch.store(1, Ordering::Relaxed);
+    let step = 4000.0 * 2f64.powf((1.0 - 64.0) / 48.0) / SAMPLE_RATE as f64;
+    // Positions that a sample's step carries to just short of the end, which
+    // rounding can turn into bit 128.
+    let end = PATTERN_BITS as f64 - step;
+    for n in 0..64 {
+        tone.pattern_position = f64::from_bits(end.to_bits() - 32 + n);
+        tone.pattern_sample(&[0xAA; 16]);
+        tone.pattern_sample(&[0xAA; 16]);
+    }
+}
@@ -0,0 +1,74 @@
+//! Reads the results test ROMs draw off the screen, and the quirk profiles
+//! `chip8 test --profiles` and the conformance tests run them under.
+
+use crate::platform::{Platform, Quirks};
+
+#[cfg(test)]
+mod tests;
+
+/// A check mark, one byte per row with the leftmost pixel in bit 7.
+pub const PASS: [u8; 5] = [0x08, 0x10, 0xA0, 0x40, 0x00];
+/// A cross.
+pub const FAIL: [u8; 5] = [0x88, 0x50, 0x20, 0x50, 0x88];
+/// 0 and 1 from the built-in font, for ROMs reporting which quirks they saw.
+pub const DIGITS: [[u8; 5]; 2] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0],
+    [0x20, 0x60, 0x20, 0x20, 0x70],
+];
+
+/// The platforms' own quirks, and the VIP with none and with all of them.
+pub fn profiles() -> Vec<(Platform, Quirks)> {
+    let mut profiles: Vec<(Platform, Quirks)> = [
+        Platform::Vip,
+        Platform::Chip48,
+        Platform::SuperChip,
+        Platform::XoChip,
+    ]
+    .into_iter()
+    .map(|platform| (platform, platform.quirks()))
+    .collect();
+    profiles.push((Platform::Vip, Quirks::default()));
+    profiles.push((
+        Platform::Vip,
+        Quirks {
+            vf_reset: true,
+            increment_i: true,
+            display_wait: true,
+            clip_sprites: true,
+            shift_vx: true,
+            jump_vx: true,
+        },
+    ));
+    profiles
+}
+
+/// The glyphs drawn in 6x6 cells from the top left of `screen`, as given by
+/// `Vm::screen_text`, 10 to a row, up to the first empty cell: `✓` and `✗`
+/// for check marks and crosses, digits, or `?` for anything else.
+pub fn results(screen: &str) -> String {
+    let rows: Vec<&[u8]> = screen.lines().map(str::as_bytes).collect();
+    let mut results = String::new();
+    for cell in 0.. {
+        let (x, y) = (cell % 10 * 6, cell / 10 * 6);
+        if y + 5 > rows.len() {
+            break;
+        }
+        let glyph: Vec<u8> = (0..5)
+            .map(|row| {
+                (0..5)
+                    .filter(|column| rows[y + row].get(x + column) == Some(&b'#'))
+                    .fold(0, |bits, column| bits | 0x80 >> column)
+            })
+            .collect();
+        results.push(match glyph[..] {
+            [0, 0, 0, 0, 0] => break,
+            _ if glyph == PASS => '✓',
+            _ if glyph == FAIL => '✗',
+            _ => match DIGITS.iter().position(|digit| glyph == digit) {
+                Some(digit) => char::from_digit(digit as u32, 10).unwrap(),
+                None => '?',
+            },
+        });
+    }
+    results
+}
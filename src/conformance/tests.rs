@@ -0,0 +1,39 @@
+use super::*;
+
+/// A 64x32 screen with `glyphs` drawn in cells along the top row.
+fn screen(glyphs: &[[u8; 5]]) -> String {
+    let mut rows = vec![vec![b'.'; 64]; 32];
+    for (cell, glyph) in glyphs.iter().enumerate() {
+        for (y, bits) in glyph.iter().enumerate() {
+            for x in 0..5 {
+                if bits & 0x80 >> x != 0 {
+                    rows[y][cell * 6 + x] = b'#';
+                }
+            }
+        }
+    }
+    rows.into_iter()
+        .map(|row| String::from_utf8(row).unwrap() + "\n")
+        .collect()
+}
+
+#[test]
+fn reads_checks_crosses_and_digits() {
+    let glyphs = [PASS, FAIL, DIGITS[1], DIGITS[0], [0xFF; 5]];
+    assert_eq!(results(&screen(&glyphs)), "✓✗10?");
+}
+
+#[test]
+fn stops_at_the_first_empty_cell() {
+    assert_eq!(results(&screen(&[PASS, [0; 5], PASS])), "✓");
+    assert_eq!(results(&screen(&[])), "");
+}
+
+#[test]
+fn profiles_cover_each_quirk_both_ways() {
+    let profiles = profiles();
+    assert!(profiles.contains(&(Platform::Vip, Quirks::default())));
+    assert!(profiles.iter().any(|(_, quirks)| quirks.vf_reset));
+    assert!(profiles.iter().any(|(_, quirks)| quirks.jump_vx));
+    assert!(profiles.iter().any(|(_, quirks)| !quirks.clip_sprites));
+}
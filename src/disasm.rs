@@ -0,0 +1,156 @@
+use std::collections::BTreeSet;
+
+use crate::analysis::{self, START_ADDRESS};
+
+const DATA_BYTES_PER_LINE: usize = 8;
+
+/// The assembly for one instruction, `None` if it isn't one. `address`
+/// formats jump, call and `LD I` targets.
+pub fn mnemonic(instruction: u16, address: &dyn Fn(u16) -> String) -> Option<String> {
+    let x = instruction >> 8 & 0xF;
+    let y = instruction >> 4 & 0xF;
+    let n = instruction & 0xF;
+    let nn = instruction & 0xFF;
+    let nnn = instruction & 0xFFF;
+    let text = match instruction {
+        0x00E0 => "CLS".to_string(),
+        0x00EE => "RET".to_string(),
+        0x00C0..=0x00CF => format!("SCD {n}"),
+        0x00D0..=0x00DF => format!("SCU {n}"),
+        0x00FB => "SCR".to_string(),
+        0x00FC => "SCL".to_string(),
+        0x00FD => "EXIT".to_string(),
+        0x00FE => "LOW".to_string(),
+        0x00FF => "HIGH".to_string(),
+        0x0000..=0x0FFF => format!("SYS {}", address(nnn)),
+        0x1000..=0x1FFF => format!("JP {}", address(nnn)),
+        0x2000..=0x2FFF => format!("CALL {}", address(nnn)),
+        0x3000..=0x3FFF => format!("SE V{x:X}, {nn:#04X}"),
+        0x4000..=0x4FFF => format!("SNE V{x:X}, {nn:#04X}"),
+        0x6000..=0x6FFF => format!("LD V{x:X}, {nn:#04X}"),
+        0x7000..=0x7FFF => format!("ADD V{x:X}, {nn:#04X}"),
+        0xA000..=0xAFFF => format!("LD I, {}", address(nnn)),
+        0xB000..=0xBFFF => format!("JP V0, {}", address(nnn)),
+        0xC000..=0xCFFF => format!("RND V{x:X}, {nn:#04X}"),
+        0xD000..=0xDFFF => format!("DRW V{x:X}, V{y:X}, {n}"),
+        0xF000 => "LD I, long".to_string(),
+        0xF002 => "AUDIO".to_string(),
+        _ => {
+            let name = match instruction & 0xF00F {
+                0x5000 => "SE",
+                0x5002 => "SAVE",
+                0x5003 => "LOAD",
+                0x8000 => "LD",
+                0x8001 => "OR",
+                0x8002 => "AND",
+                0x8003 => "XOR",
+                0x8004 => "ADD",
+                0x8005 => "SUB",
+                0x8006 => "SHR",
+                0x8007 => "SUBN",
+                0x800E => "SHL",
+                0x9000 => "SNE",
+                _ => "",
+            };
+            if !name.is_empty() {
+                format!("{name} V{x:X}, V{y:X}")
+            } else {
+                match instruction & 0xF0FF {
+                    0xE09E => format!("SKP V{x:X}"),
+                    0xE0A1 => format!("SKNP V{x:X}"),
+                    0xF001 => format!("PLANE {x}"),
+                    0xF007 => format!("LD V{x:X}, DT"),
+                    0xF00A => format!("LD V{x:X}, K"),
+                    0xF015 => format!("LD DT, V{x:X}"),
+                    0xF018 => format!("LD ST, V{x:X}"),
+                    0xF01E => format!("ADD I, V{x:X}"),
+                    0xF029 => format!("LD F, V{x:X}"),
+                    0xF030 => format!("LD HF, V{x:X}"),
+                    0xF033 => format!("LD B, V{x:X}"),
+                    0xF03A => format!("PITCH V{x:X}"),
+                    0xF055 => format!("LD [I], V{x:X}"),
+                    0xF065 => format!("LD V{x:X}, [I]"),
+                    0xF075 => format!("LD R, V{x:X}"),
+                    0xF085 => format!("LD V{x:X}, R"),
+                    _ => return None,
+                }
+            }
+        }
+    };
+    Some(text)
+}
+
+/// Disassembles a ROM into source that `asm::assemble` turns back into the
+/// same bytes. Reachable code becomes instructions and everything else `DB`
+/// lines, with labels on the addresses the code refers to.
+pub fn disassemble(rom: &[u8]) -> String {
+    let end = START_ADDRESS as usize + rom.len();
+    let word_at = |address: usize| -> u16 {
+        let offset = address - START_ADDRESS as usize;
+        ((rom[offset] as u16) << 8) | rom[offset + 1] as u16
+    };
+    let code = analysis::trace(rom);
+    let labels: BTreeSet<u16> = code
+        .iter()
+        .map(|&address| word_at(address as usize))
+        .filter(|instruction| matches!(instruction & 0xF000, 0x1000 | 0x2000 | 0xA000 | 0xB000))
+        .map(|instruction| instruction & 0xFFF)
+        .filter(|&target| (START_ADDRESS as usize..end).contains(&(target as usize)))
+        .collect();
+    let address = |target: u16| {
+        if labels.contains(&target) {
+            label(target)
+        } else {
+            format!("{target:#05X}")
+        }
+    };
+    let mut source = String::new();
+    let mut pc = START_ADDRESS as usize;
+    while pc < end {
+        if labels.contains(&(pc as u16)) {
+            source.push_str(&format!("{}:\n", label(pc as u16)));
+        }
+        let instruction = if code.contains(&(pc as u16)) {
+            let instruction = word_at(pc);
+            // A long `LD I` is followed by its 16 bit operand.
+            let len = if instruction == 0xF000 { 4 } else { 2 };
+            let fits = pc + len <= end
+                && (pc + 1..pc + len).all(|address| !labels.contains(&(address as u16)));
+            mnemonic(instruction, &address)
+                .filter(|_| fits)
+                .map(|text| match len {
+                    4 => (format!("{text} {:#06X}", word_at(pc + 2)), len),
+                    _ => (text, len),
+                })
+        } else {
+            None
+        };
+        let (text, len) = instruction.unwrap_or_else(|| {
+            let len = (pc + 1..end)
+                .take(DATA_BYTES_PER_LINE - 1)
+                .take_while(|&address| {
+                    !labels.contains(&(address as u16)) && !code.contains(&(address as u16))
+                })
+                .count()
+                + 1;
+            let offset = pc - START_ADDRESS as usize;
+            let bytes: Vec<String> = rom[offset..offset + len]
+                .iter()
+                .map(|byte| format!("{byte:#04X}"))
+                .collect();
+            (format!("DB {}", bytes.join(", ")), len)
+        });
+        let offset = pc - START_ADDRESS as usize;
+        let hex: String = rom[offset..offset + len]
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        source.push_str(&format!("    {text:<23} ; {pc:03X}: {hex}\n"));
+        pc += len;
+    }
+    source
+}
+
+fn label(address: u16) -> String {
+    format!("L{address:03X}")
+}
@@ -0,0 +1,32 @@
+use std::{env, process};
+
+use super::*;
+
+#[test]
+fn font_sets_parse_by_name() {
+    for (name, font_set) in [
+        ("vip", FontSet::Vip),
+        ("chip48", FontSet::Chip48),
+        ("schip", FontSet::SuperChip),
+        ("superchip", FontSet::SuperChip),
+        ("dream6800", FontSet::Dream6800),
+        ("eti660", FontSet::Eti660),
+    ] {
+        assert_eq!(name.parse(), Ok(font_set));
+    }
+    assert!("chip8".parse::<FontSet>().is_err());
+    assert_ne!(FontSet::Vip.font(), FontSet::Chip48.font());
+}
+
+#[test]
+fn font_files_have_to_hold_a_whole_font() {
+    let path = env::temp_dir().join(format!("chip8-font-{}", process::id()));
+    fs::write(&path, [0xAA; FONT_LEN]).unwrap();
+    assert_eq!(load(&path), Ok([0xAA; FONT_LEN]));
+    fs::write(&path, [0xAA; FONT_LEN - 1]).unwrap();
+    let error = load(&path).unwrap_err();
+    assert!(error.ends_with("is 79 bytes long, a font is 80"), "{error}");
+    fs::remove_file(&path).unwrap();
+    let error = load(&path).unwrap_err();
+    assert!(error.starts_with("could not read"), "{error}");
+}
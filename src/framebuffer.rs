@@ -0,0 +1,83 @@
+pub const MAX_WIDTH: usize = 128;
+pub const MAX_HEIGHT: usize = 64;
+
+/// The display, one bit per pixel with each row packed into a `u128`, the
+/// leftmost pixel in the top bit. Up to 128x64 pixels.
+#[derive(Clone, PartialEq)]
+pub struct Framebuffer {
+    rows: [u128; MAX_HEIGHT],
+    width: usize,
+    height: usize,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        assert!(width <= MAX_WIDTH && height <= MAX_HEIGHT);
+        Framebuffer {
+            rows: [0; MAX_HEIGHT],
+            width,
+            height,
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn clear(&mut self) {
+        self.rows = [0; MAX_HEIGHT];
+    }
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.rows[y] >> (MAX_WIDTH - 1 - x) & 1 == 1
+    }
+    pub fn set_pixel(&mut self, x: usize, y: usize, lit: bool) {
+        let bit = 1 << (MAX_WIDTH - 1 - x);
+        if lit {
+            self.rows[y] |= bit;
+        } else {
+            self.rows[y] &= !bit;
+        }
+    }
+    /// XORs an 8 pixel wide sprite onto the display at (`x`, `y`), which
+    /// wrap around. Parts going off the edges are cut off if `clip`, drawn
+    /// on the other side otherwise. Returns whether a lit pixel was erased.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8], clip: bool) -> bool {
+        let (x, y) = (x % self.width, y % self.height);
+        let visible = !0 << (MAX_WIDTH - self.width);
+        let mut erased = false;
+        for (i, &byte) in sprite.iter().enumerate() {
+            if clip && y + i >= self.height {
+                break;
+            }
+            let sprite_row = (byte as u128) << (MAX_WIDTH - 8);
+            let mut bits = sprite_row >> x;
+            if !clip {
+                // The pixels past the right edge, moved to the left one.
+                bits |= sprite_row.checked_shl((self.width - x) as u32).unwrap_or(0);
+            }
+            let row = &mut self.rows[(y + i) % self.height];
+            let bits = bits & visible;
+            erased |= *row & bits != 0;
+            *row ^= bits;
+        }
+        erased
+    }
+    /// The coordinates of the lit pixels.
+    pub fn lit_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.rows[..self.height]
+            .iter()
+            .enumerate()
+            .flat_map(move |(y, &row)| {
+                (0..self.width)
+                    .filter(move |&x| row >> (MAX_WIDTH - 1 - x) & 1 == 1)
+                    .map(move |x| (x, y))
+            })
+    }
+    /// The display with one byte per pixel, 1 for lit ones.
+    pub fn to_bytes(&self) -> Vec<Vec<u8>> {
+        (0..self.height)
+            .map(|y| (0..self.width).map(|x| self.pixel(x, y) as u8).collect())
+            .collect()
+    }
+}
@@ -0,0 +1,131 @@
+use std::str::FromStr;
+
+use crate::keypad::{KeySchedule, Keypad};
+
+pub enum MainLoopAction {
+    Interrupt,
+    Continue,
+    SaveState,
+    LoadState,
+    TogglePause,
+    /// Runs a single frame and pauses.
+    FrameAdvance,
+    ToggleSlowMotion,
+    /// Restarts the ROM from a freshly loaded machine.
+    Reset,
+    Screenshot,
+}
+
+/// Background and foreground colours as RGB.
+#[derive(Clone, Copy)]
+pub struct Palette {
+    pub background: [u8; 3],
+    pub foreground: [u8; 3],
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            background: [0, 0, 0],
+            foreground: [255, 255, 255],
+        }
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Parses the background and foreground colours as `RRGGBB RRGGBB`.
+    fn from_str(s: &str) -> Result<Palette, String> {
+        let colors = s
+            .split([',', ' '])
+            .filter(|c| !c.is_empty())
+            .map(|c| {
+                let hex = c.trim_start_matches('#');
+                let rgb = u32::from_str_radix(hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 6)
+                    .ok_or(format!("invalid colour `{c}`, expected RRGGBB"))?;
+                Ok([(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8])
+            })
+            .collect::<Result<Vec<[u8; 3]>, String>>()?;
+        match colors[..] {
+            [background, foreground] => Ok(Palette {
+                background,
+                foreground,
+            }),
+            _ => Err(format!(
+                "invalid palette `{s}`, expected a background and a foreground colour"
+            )),
+        }
+    }
+}
+
+/// Where the VM's display goes and where its input comes from.
+pub trait Frontend {
+    /// Handles pending input events, called once per frame before the VM runs.
+    fn draw(&mut self) -> MainLoopAction;
+    fn draw_points(&mut self, points: &[(i32, i32)]);
+    /// The keypad keys held down, read once per frame after `draw`.
+    fn keypad(&mut self) -> Keypad;
+    fn set_resolution(&mut self, _width: usize, _height: usize) {}
+    fn set_title(&mut self, _title: &str) {}
+    fn set_palette(&mut self, _palette: Palette) {}
+    /// Sets what the keypad keys do, for frontends that can show them.
+    fn set_key_hints(&mut self, _key_hints: Option<String>) {}
+    /// Whether the fast-forward key is held down.
+    fn is_fast_forward_held(&mut self) -> bool {
+        false
+    }
+    /// Shows what the emulator is doing, e.g. `PAUSED`, until replaced or
+    /// cleared with `None`.
+    fn set_status(&mut self, _status: Option<String>) {}
+    /// Whether frames should be paced at 60 Hz rather than run flat out.
+    fn is_realtime(&self) -> bool {
+        true
+    }
+}
+
+/// Runs without a display, for a fixed number of frames if given, with the
+/// keys a schedule holds down as input.
+pub struct Headless {
+    frames: Option<u64>,
+    /// Frames drawn so far.
+    frame: u64,
+    keys: KeySchedule,
+}
+
+impl Headless {
+    pub fn new(frames: Option<u64>) -> Headless {
+        Headless {
+            frames,
+            frame: 0,
+            keys: KeySchedule::default(),
+        }
+    }
+    /// Holds down the keys `keys` gives for each frame.
+    pub fn with_keys(self, keys: KeySchedule) -> Headless {
+        Headless { keys, ..self }
+    }
+}
+
+impl Frontend for Headless {
+    fn draw(&mut self) -> MainLoopAction {
+        self.frame += 1;
+        match &mut self.frames {
+            Some(0) => MainLoopAction::Interrupt,
+            Some(frames) => {
+                *frames -= 1;
+                MainLoopAction::Continue
+            }
+            None => MainLoopAction::Continue,
+        }
+    }
+    fn draw_points(&mut self, _points: &[(i32, i32)]) {}
+    fn keypad(&mut self) -> Keypad {
+        self.keys.keypad(self.frame)
+    }
+    fn is_realtime(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,46 @@
+use std::str::FromStr;
+
+/// Which keyboard key stands for each CHIP-8 keypad key.
+#[derive(Clone, Copy)]
+pub struct Keymap([char; 16]);
+
+impl Keymap {
+    /// The keyboard key mapped to keypad `key`.
+    pub fn char_for(&self, key: u8) -> char {
+        self.0[key as usize & 0xF]
+    }
+}
+
+impl Default for Keymap {
+    /// The left-hand block of a QWERTY keyboard, laid out like the keypad:
+    ///
+    /// ```text
+    /// 1 2 3 C      1 2 3 4
+    /// 4 5 6 D      Q W E R
+    /// 7 8 9 E  ->  A S D F
+    /// A 0 B F      Z X C V
+    /// ```
+    fn default() -> Keymap {
+        "x123qweasdzc4rfv".parse().unwrap()
+    }
+}
+
+impl FromStr for Keymap {
+    type Err = String;
+
+    /// Parses the keys for keypad 0 to F in order, e.g. `x123qweasdzc4rfv`.
+    fn from_str(s: &str) -> Result<Keymap, String> {
+        let keys: Vec<char> = s.chars().map(|c| c.to_ascii_lowercase()).collect();
+        let keys: [char; 16] = keys
+            .try_into()
+            .map_err(|_| format!("invalid keymap `{s}`, expected 16 keys for 0 to F"))?;
+        if let Some(c) = keys
+            .iter()
+            .enumerate()
+            .find_map(|(i, c)| keys[..i].contains(c).then_some(c))
+        {
+            return Err(format!("invalid keymap `{s}`, `{c}` is used twice"));
+        }
+        Ok(Keymap(keys))
+    }
+}
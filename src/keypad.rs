@@ -0,0 +1,74 @@
+use std::{ops::Range, str::FromStr};
+
+#[cfg(test)]
+mod tests;
+
+/// Which of the 16 keypad keys are held down, one bit per key.
+///
+/// The VM takes a snapshot from its frontend once per frame, so programs see
+/// the same keys for the whole frame whatever the input comes from.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Keypad(u16);
+
+impl Keypad {
+    /// A keypad with the keys `pressed` returns true for held down.
+    pub fn from_fn(mut pressed: impl FnMut(u8) -> bool) -> Keypad {
+        Keypad(
+            (0..16)
+                .filter(|&key| pressed(key))
+                .fold(0, |bits, key| bits | 1 << key),
+        )
+    }
+    /// Whether `key` is held down. Only the low nibble of `key` counts, as on
+    /// the COSMAC VIP.
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.0 >> (key & 0xF) & 1 == 1
+    }
+    /// The lowest key held down, if any.
+    pub fn first_pressed(&self) -> Option<u8> {
+        (self.0 != 0).then(|| self.0.trailing_zeros() as u8)
+    }
+}
+
+/// Keys held down over ranges of frames, for replaying the same input to
+/// machines run without a frontend.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct KeySchedule(Vec<(u8, Range<u64>)>);
+
+impl KeySchedule {
+    /// The keys held down during `frame`.
+    pub fn keypad(&self, frame: u64) -> Keypad {
+        Keypad::from_fn(|key| {
+            self.0
+                .iter()
+                .any(|(held, frames)| *held == key && frames.contains(&frame))
+        })
+    }
+}
+
+impl FromStr for KeySchedule {
+    type Err = String;
+
+    /// Parses a comma separated list of a hex key, `@` and the frames it is
+    /// held for, e.g. `5@10-20,A@40-60` holds 5 from frame 10 until frame 20
+    /// and A from frame 40 until frame 60.
+    fn from_str(s: &str) -> Result<KeySchedule, String> {
+        let mut schedule = vec![];
+        for entry in s.split(',').filter(|e| !e.is_empty()) {
+            let invalid = || format!("invalid key press `{entry}`, expected e.g. 5@10-20");
+            let (key, frames) = entry.split_once('@').ok_or_else(invalid)?;
+            let (start, end) = frames.split_once('-').ok_or_else(invalid)?;
+            let key = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|&key| key < 16)
+                .ok_or_else(invalid)?;
+            let start: u64 = start.parse().map_err(|_| invalid())?;
+            let end: u64 = end.parse().map_err(|_| invalid())?;
+            if end <= start {
+                return Err(format!("key press `{entry}` ends before it starts"));
+            }
+            schedule.push((key, start..end));
+        }
+        Ok(KeySchedule(schedule))
+    }
+}
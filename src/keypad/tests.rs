@@ -0,0 +1,49 @@
+use super::*;
+
+#[test]
+fn press_and_release() {
+    let keypad = Keypad::from_fn(|key| key == 0x5 || key == 0xA);
+    assert!(keypad.is_pressed(0x5));
+    assert!(keypad.is_pressed(0xA));
+    assert!(!keypad.is_pressed(0x0));
+    assert!(!keypad.is_pressed(0xF));
+    let released = Keypad::from_fn(|key| key == 0xA);
+    assert!(!released.is_pressed(0x5));
+    assert!(released.is_pressed(0xA));
+    assert!((0..16).all(|key| !Keypad::default().is_pressed(key)));
+}
+
+#[test]
+fn only_the_low_nibble_counts() {
+    let keypad = Keypad::from_fn(|key| key == 0x3);
+    assert!(keypad.is_pressed(0x13));
+    assert!(keypad.is_pressed(0xF3));
+}
+
+#[test]
+fn first_pressed_is_the_lowest_key() {
+    assert_eq!(Keypad::default().first_pressed(), None);
+    let keypad = Keypad::from_fn(|key| key == 0x9 || key == 0xC);
+    assert_eq!(keypad.first_pressed(), Some(0x9));
+    assert_eq!(Keypad::from_fn(|key| key == 0xF).first_pressed(), Some(0xF));
+}
+
+#[test]
+fn schedule_holds_keys_for_their_frames() {
+    let schedule: KeySchedule = "5@10-20,a@15-16".parse().unwrap();
+    assert_eq!(schedule.keypad(9), Keypad::default());
+    assert_eq!(schedule.keypad(10), Keypad::from_fn(|key| key == 0x5));
+    assert_eq!(
+        schedule.keypad(15),
+        Keypad::from_fn(|key| key == 0x5 || key == 0xA)
+    );
+    assert_eq!(schedule.keypad(20), Keypad::default());
+    assert_eq!("".parse(), Ok(KeySchedule::default()));
+}
+
+#[test]
+fn schedule_rejects_bad_presses() {
+    for s in ["5", "5@10", "G@1-2", "10@1-2", "5@x-2", "5@3-3", "5@4-2"] {
+        assert!(s.parse::<KeySchedule>().is_err(), "{s}");
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sdl2::{event::Event, keyboard::Keycode, pixels::Color, rect::Rect};
+
+use crate::{
+    screen::Screen,
+    text::{self, CHAR_WIDTH, LINE_HEIGHT},
+};
+
+const TEXT_SCALE: u32 = 2;
+const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+struct Entry {
+    name: String,
+    path: PathBuf,
+    description: Option<String>,
+}
+
+/// Lists the ROMs in `dir` and lets the user pick one. Returns `None` if the
+/// window is closed or Escape is pressed with an empty search.
+pub fn choose_rom(screen: &mut Screen, dir: &Path) -> Result<Option<PathBuf>, String> {
+    let entries = read_entries(dir)?;
+    if entries.is_empty() {
+        return Err(format!("no ROMs found in {}", dir.display()));
+    }
+    let mut query = String::new();
+    let mut selected = 0;
+    let mut scroll = 0;
+    loop {
+        let lowercase_query = query.to_lowercase();
+        let matches: Vec<&Entry> = entries
+            .iter()
+            .filter(|e| e.name.to_lowercase().contains(&lowercase_query))
+            .collect();
+        selected = selected.min(matches.len().saturating_sub(1));
+        let list_rows = render(screen, &query, &matches, selected, &mut scroll)?;
+        match screen.wait_event() {
+            Event::Quit { .. } => return Ok(None),
+            Event::KeyDown {
+                keycode: Some(keycode),
+                ..
+            } => match keycode {
+                Keycode::Escape if query.is_empty() => return Ok(None),
+                Keycode::Escape => query.clear(),
+                Keycode::Up => selected = selected.saturating_sub(1),
+                Keycode::Down => selected += 1,
+                Keycode::PageUp => selected = selected.saturating_sub(list_rows),
+                Keycode::PageDown => selected += list_rows,
+                Keycode::Home => selected = 0,
+                Keycode::End => selected = matches.len(),
+                Keycode::Backspace => {
+                    query.pop();
+                }
+                Keycode::Return | Keycode::KpEnter => {
+                    if let Some(entry) = matches.get(selected) {
+                        return Ok(Some(entry.path.clone()));
+                    }
+                }
+                _ => {}
+            },
+            Event::TextInput { text, .. } => {
+                query.push_str(&text);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn read_entries(dir: &Path) -> Result<Vec<Entry>, String> {
+    let read_dir =
+        fs::read_dir(dir).map_err(|e| format!("could not open {}: {e}", dir.display()))?;
+    let mut entries: Vec<Entry> = read_dir
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| ROM_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        })
+        .map(|path| Entry {
+            name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            description: fs::read(path.with_extension("txt"))
+                .ok()
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+            path,
+        })
+        .collect();
+    entries.sort_by_key(|e| e.name.to_lowercase());
+    Ok(entries)
+}
+
+/// Draws the search line, the visible part of the list and a preview of the
+/// selected ROM's description. Returns how many list rows fit on screen.
+fn render(
+    screen: &mut Screen,
+    query: &str,
+    matches: &[&Entry],
+    selected: usize,
+    scroll: &mut usize,
+) -> Result<usize, String> {
+    let canvas = screen.canvas();
+    let (width, height) = canvas.output_size()?;
+    let line_height = LINE_HEIGHT * TEXT_SCALE;
+    let columns = (width / (CHAR_WIDTH * TEXT_SCALE)) as usize;
+    let rows = (height / line_height) as usize;
+    let list_rows = (rows / 2).saturating_sub(1).max(1);
+    if selected < *scroll {
+        *scroll = selected;
+    } else if selected >= *scroll + list_rows {
+        *scroll = selected + 1 - list_rows;
+    }
+    let y_of = |row: usize| (row as u32 * line_height + TEXT_SCALE) as i32;
+
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    let search = format!("Search: {query}_  ({} ROMs)", matches.len());
+    text::draw_text(canvas, 0, y_of(0), TEXT_SCALE, &truncate(&search, columns))?;
+    for (row, entry) in matches.iter().enumerate().skip(*scroll).take(list_rows) {
+        let y = y_of(row - *scroll + 1);
+        if row == selected {
+            canvas.set_draw_color(Color::RGB(255, 255, 255));
+            canvas.fill_rect(Rect::new(0, y - TEXT_SCALE as i32, width, line_height))?;
+            canvas.set_draw_color(Color::RGB(0, 0, 0));
+        } else {
+            canvas.set_draw_color(Color::RGB(200, 200, 200));
+        }
+        text::draw_text(canvas, 0, y, TEXT_SCALE, &truncate(&entry.name, columns))?;
+    }
+    let preview_row = list_rows + 2;
+    canvas.set_draw_color(Color::RGB(80, 80, 80));
+    canvas.fill_rect(Rect::new(
+        0,
+        y_of(preview_row) - (line_height / 2) as i32,
+        width,
+        TEXT_SCALE,
+    ))?;
+    canvas.set_draw_color(Color::RGB(160, 160, 160));
+    let description = match matches.get(selected).and_then(|e| e.description.as_ref()) {
+        Some(description) => description.as_str(),
+        None => "No description.",
+    };
+    let lines = text::wrap(description, columns);
+    for (row, line) in lines
+        .iter()
+        .take(rows.saturating_sub(preview_row))
+        .enumerate()
+    {
+        text::draw_text(canvas, 0, y_of(preview_row + row), TEXT_SCALE, line)?;
+    }
+    canvas.present();
+    Ok(list_rows)
+}
+
+fn truncate(text: &str, columns: usize) -> String {
+    text.chars().take(columns).collect()
+}
@@ -0,0 +1,23 @@
+//! A CHIP-8, SUPER-CHIP and XO-CHIP emulator, built into the `chip8`
+//! binary. The library lets benchmarks and tests drive the VM directly.
+
+pub mod analysis;
+pub mod asm;
+pub mod audio;
+pub mod conformance;
+pub mod disasm;
+pub mod font;
+pub mod framebuffer;
+pub mod frontend;
+pub mod keymap;
+pub mod keypad;
+pub mod launcher;
+pub mod platform;
+pub mod recorder;
+pub mod rng;
+pub mod romdb;
+pub mod screen;
+pub mod screenshot;
+pub mod terminal;
+pub mod text;
+pub mod vm;
@@ -1,19 +1,625 @@
-use std::{env, fs, process};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
 
-use vm::Vm;
+use chip8::{
+    analysis::{self, SelfModifying},
+    asm,
+    audio::{
+        self, AudioBackend, AudioConfig, AudioSink, NullSink, TeeSink, WavSink, Waveform,
+        DEFAULT_FREQUENCY, DEFAULT_VOLUME,
+    },
+    conformance, disasm,
+    font::{self, FontSet},
+    frontend::{Headless, Palette},
+    keymap::Keymap,
+    keypad::KeySchedule,
+    launcher,
+    platform::{Platform, Quirks},
+    recorder::RecordConfig,
+    rng::RngMode,
+    romdb,
+    screen::{Screen, DEFAULT_SCALE},
+    screenshot::ScreenshotConfig,
+    terminal::{Terminal, TerminalMode},
+    vm::{lockstep, Engine, Options, Vm, DEFAULT_FAST_FORWARD, DEFAULT_SLOW_MOTION},
+};
+use clap::{Args, Parser, Subcommand};
 
-mod audio;
-mod screen;
-mod vm;
+const DEFAULT_ROM_DIR: &str = "examples";
+
+/// A CHIP-8, SUPER-CHIP and XO-CHIP emulator.
+///
+/// Without a command it runs, so `chip8 rom.ch8` plays a ROM and `chip8` on
+/// its own opens the launcher.
+#[derive(Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Runs a ROM, or picks one from a directory in the launcher.
+    Run(Box<RunArgs>),
+    /// Prints a ROM as assembly that `asm` turns back into the same bytes.
+    Disasm { rom: PathBuf },
+    /// Assembles a source file into a ROM.
+    Asm {
+        source: PathBuf,
+        /// Where to write the ROM, the source with a .ch8 extension if not
+        /// given.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Prints what is known about a ROM.
+    Info { rom: PathBuf },
+    /// Runs a ROM headless for a number of frames and prints the screen.
+    Test {
+        rom: PathBuf,
+        #[arg(long, default_value_t = 300)]
+        frames: u64,
+        /// A file holding the expected screen, exits with an error if it
+        /// differs.
+        #[arg(long)]
+        expect: Option<PathBuf>,
+        /// Runs the ROM under each quirk profile instead and prints the check
+        /// marks, crosses and digits it draws, exits with an error if any
+        /// check fails.
+        #[arg(long, conflicts_with_all = ["expect", "platform", "quirks"])]
+        profiles: bool,
+        /// Keys to hold down, a comma separated list of a hex key, `@` and
+        /// the frames it is held for, e.g. 5@10-20,A@40-60.
+        #[arg(long, default_value = "")]
+        keys: KeySchedule,
+        #[command(flatten)]
+        machine: MachineArgs,
+    },
+    /// Runs a ROM on two machines in lockstep, e.g. with different quirks,
+    /// and reports the first instruction after which they differ.
+    Diff {
+        rom: PathBuf,
+        #[arg(long, default_value_t = 600)]
+        frames: u64,
+        /// Keys both machines hold down, a comma separated list of a hex key,
+        /// `@` and the frames it is held for, e.g. 5@10-20,A@40-60.
+        #[arg(long, default_value = "")]
+        keys: KeySchedule,
+        #[command(flatten)]
+        machine: MachineArgs,
+        #[command(flatten)]
+        other: OtherMachineArgs,
+    },
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// A ROM file, or a directory to choose one from.
+    #[arg(default_value = DEFAULT_ROM_DIR)]
+    path: PathBuf,
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// Window pixels per CHIP-8 pixel.
+    #[arg(long, default_value_t = DEFAULT_SCALE)]
+    scale: usize,
+    /// Colours as `RRGGBB RRGGBB`, background then foreground.
+    #[arg(long)]
+    palette: Option<Palette>,
+    /// The keyboard keys for keypad 0 to F.
+    #[arg(long, default_value = "x123qweasdzc4rfv")]
+    keymap: Keymap,
+    /// Runs without a window or sound.
+    #[arg(long, conflicts_with = "terminal")]
+    headless: bool,
+    /// Draws in the terminal instead of a window, with half blocks or
+    /// braille.
+    #[arg(long, value_name = "MODE", num_args = 0..=1, default_missing_value = "half")]
+    terminal: Option<TerminalMode>,
+    /// Stops a headless run after this many frames.
+    #[arg(long, requires = "headless")]
+    frames: Option<u64>,
+    /// F5 saves the machine state to this file and F9 loads it. Headless
+    /// runs resume from it if it exists and save to it on exit.
+    #[arg(long)]
+    state: Option<PathBuf>,
+    /// Frames run per frame shown while Tab is held.
+    #[arg(long, default_value_t = DEFAULT_FAST_FORWARD)]
+    fast_forward: u32,
+    /// How many times slower slow motion (F6) runs.
+    #[arg(long, default_value_t = DEFAULT_SLOW_MOTION)]
+    slow_motion: u32,
+    /// Where F12 saves screenshots.
+    #[arg(long, default_value = ".")]
+    screenshot_dir: PathBuf,
+    /// Saves a screenshot when the run ends.
+    #[arg(long)]
+    screenshot_on_exit: bool,
+    /// Records every frame to an animated GIF if the file ends in .gif, as
+    /// raw 24 bit RGB frames otherwise, or as raw frames to stdout for `-`.
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// Records the sound to a WAV file.
+    #[arg(long)]
+    record_audio: Option<PathBuf>,
+    /// auto, rodio, null or wav:<file>.
+    #[arg(long, default_value = "auto")]
+    audio: AudioBackend,
+    /// The beep's waveform: square, sine or triangle.
+    #[arg(long, default_value = "square")]
+    waveform: Waveform,
+    /// The beep's pitch in Hz.
+    #[arg(long, value_parser = parse_frequency, default_value_t = DEFAULT_FREQUENCY)]
+    frequency: f32,
+    /// The beep's volume, from 0.0 to 1.0.
+    #[arg(long, value_parser = parse_volume, default_value_t = DEFAULT_VOLUME)]
+    volume: f32,
+}
+
+/// Settings overriding the ROM database and platform defaults.
+#[derive(Args)]
+struct MachineArgs {
+    /// vip, vip-hires, chip48, schip or xochip.
+    #[arg(long)]
+    platform: Option<Platform>,
+    /// Instructions per frame.
+    #[arg(long)]
+    speed: Option<u32>,
+    /// A comma separated list of vf-reset, increment-i, display-wait, clip,
+    /// shift and jump, or none.
+    #[arg(long)]
+    quirks: Option<Quirks>,
+    /// Seeds the random numbers, making runs repeatable. Random if not
+    /// given, except for `test` and `diff` which use 0.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Where random numbers come from: seeded, or vip to use the COSMAC VIP
+    /// interpreter's routine, whose numbers depend on timing.
+    #[arg(long, default_value = "seeded")]
+    rng: RngMode,
+    /// How instructions run: interpreter decodes each one every time, cached
+    /// decodes each once and blocks translates straight-line code.
+    #[arg(long, default_value = "interpreter")]
+    engine: Engine,
+    /// The built-in font: vip, chip48, schip, dream6800 or eti660.
+    #[arg(long, default_value = "chip48")]
+    font: FontSet,
+    /// A file holding the 80 bytes of a font, used instead of the built-in
+    /// one.
+    #[arg(long, conflicts_with = "font")]
+    font_file: Option<PathBuf>,
+    /// Where the font goes in memory, e.g. 0x050.
+    #[arg(long, value_parser = parse_address, default_value = "0x000")]
+    font_address: u16,
+}
+
+/// The second machine `diff` runs, the same as the first except for what is
+/// given.
+#[derive(Args)]
+struct OtherMachineArgs {
+    /// The second machine's platform.
+    #[arg(long)]
+    other_platform: Option<Platform>,
+    /// The second machine's instructions per frame.
+    #[arg(long)]
+    other_speed: Option<u32>,
+    /// The second machine's quirks.
+    #[arg(long)]
+    other_quirks: Option<Quirks>,
+    /// The second machine's random numbers.
+    #[arg(long)]
+    other_rng: Option<RngMode>,
+    /// The second machine's engine.
+    #[arg(long)]
+    other_engine: Option<Engine>,
+    /// The second machine's built-in font.
+    #[arg(long)]
+    other_font: Option<FontSet>,
+    /// A file holding the second machine's font.
+    #[arg(long, conflicts_with = "other_font")]
+    other_font_file: Option<PathBuf>,
+    /// Where the second machine's font goes in memory.
+    #[arg(long, value_parser = parse_address)]
+    other_font_address: Option<u16>,
+}
+
+impl MachineArgs {
+    fn options(&self) -> Result<Options, String> {
+        let font = match &self.font_file {
+            Some(path) => font::load(path)?,
+            None => *self.font.font(),
+        };
+        Ok(Options {
+            platform: self.platform,
+            quirks: self.quirks,
+            speed: self.speed,
+            seed: self.seed,
+            rng: self.rng,
+            engine: self.engine,
+            font,
+            font_address: self.font_address,
+            ..Options::default()
+        })
+    }
+}
 
 fn main() {
-    let file = env::args().nth(1).unwrap();
-    let rom = fs::read(file).unwrap();
-    if rom.len() > 3584 {
-        eprintln!("Rom is too large");
+    let cli = Cli::parse();
+    let Some(command) = cli.command else {
+        return exit_on_error(run(cli.run));
+    };
+    let result = match command {
+        Command::Run(args) => run(*args),
+        Command::Disasm { rom } => {
+            read_rom(&rom).map(|rom| print!("{}", disasm::disassemble(&rom)))
+        }
+        Command::Asm { source, output } => asm(&source, output),
+        Command::Info { rom } => read_rom(&rom).map(|rom| info(&rom)),
+        Command::Test {
+            rom,
+            frames,
+            expect,
+            profiles,
+            keys,
+            machine,
+        } => {
+            let frontend = || Box::new(Headless::new(Some(frames)).with_keys(keys.clone()));
+            if profiles {
+                test_profiles(&rom, frontend, machine)
+            } else {
+                test(&rom, frontend(), expect, machine)
+            }
+        }
+        Command::Diff {
+            rom,
+            frames,
+            keys,
+            machine,
+            other,
+        } => diff(&rom, frames, &keys, machine, other),
+    };
+    exit_on_error(result);
+}
+
+fn exit_on_error(result: Result<(), String>) {
+    if let Err(e) = result {
+        eprintln!("error: {e}");
         process::exit(1);
     }
-    // println!("{}", rom.len());
-    let vm = Vm::new(rom);
-    vm.start();
+}
+
+fn run(args: RunArgs) -> Result<(), String> {
+    let mut options = Options {
+        palette: args.palette,
+        state_file: args.state.clone(),
+        fast_forward: args.fast_forward,
+        slow_motion: args.slow_motion,
+        record: args.record.clone().map(|path| RecordConfig {
+            path,
+            scale: args.scale,
+        }),
+        ..args.machine.options()?
+    };
+    if args.headless {
+        let rom = read_rom(&args.path)?;
+        options.screenshot = Some(screenshot_config(&args, &args.path));
+        let audio = open_audio(&args, &AudioBackend::Null)?;
+        let mut vm = Vm::new(rom, Box::new(Headless::new(args.frames)), audio, options)?;
+        if args.state.as_ref().is_some_and(|state| state.exists()) {
+            vm.load_state_file();
+        }
+        vm.start()?;
+        if args.state.is_some() {
+            vm.save_state_file();
+        }
+        if args.screenshot_on_exit {
+            report_screenshot(vm.screenshot())?;
+        }
+        // Recorded frames may be going to stdout.
+        if args.record.as_deref() != Some(Path::new("-")) {
+            print!("{}", vm.screen_text());
+        }
+        return Ok(());
+    }
+    if let Some(mode) = args.terminal {
+        let rom = read_rom(&args.path)?;
+        options.screenshot = Some(screenshot_config(&args, &args.path));
+        let audio = open_audio(&args, &args.audio)?;
+        let terminal = Terminal::new(mode, args.keymap)?;
+        let mut vm = Vm::new(rom, Box::new(terminal), audio, options)?;
+        vm.start()?;
+        let screenshot = args.screenshot_on_exit.then(|| vm.screenshot());
+        // Restores the terminal before printing anything.
+        drop(vm);
+        if let Some(screenshot) = screenshot {
+            report_screenshot(screenshot)?;
+        }
+        return Ok(());
+    }
+    let mut screen = Screen::new(args.scale, args.keymap);
+    let file = if args.path.is_dir() {
+        match launcher::choose_rom(&mut screen, &args.path)? {
+            Some(file) => file,
+            None => return Ok(()),
+        }
+    } else {
+        args.path.clone()
+    };
+    let rom = read_rom(&file)?;
+    options.screenshot = Some(screenshot_config(&args, &file));
+    screen.set_help_text(
+        fs::read(file.with_extension("txt"))
+            .ok()
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+    );
+    let audio = open_audio(&args, &args.audio)?;
+    let mut vm = Vm::new(rom, Box::new(screen), audio, options)?;
+    vm.start()?;
+    if args.screenshot_on_exit {
+        report_screenshot(vm.screenshot())?;
+    }
+    Ok(())
+}
+
+fn report_screenshot(screenshot: Result<Vec<PathBuf>, String>) -> Result<(), String> {
+    for path in screenshot.map_err(|e| format!("could not save screenshot: {e}"))? {
+        eprintln!("Saved {}", path.display());
+    }
+    Ok(())
+}
+
+fn open_audio(args: &RunArgs, backend: &AudioBackend) -> Result<Box<dyn AudioSink>, String> {
+    let config = AudioConfig {
+        waveform: args.waveform,
+        frequency: args.frequency,
+        volume: args.volume,
+    };
+    let audio =
+        audio::open_sink(backend, config).map_err(|e| format!("could not open audio: {e}"))?;
+    Ok(match args.record_audio.as_deref() {
+        Some(path) => Box::new(TeeSink(vec![audio, Box::new(WavSink::new(path, config)?)])),
+        None => audio,
+    })
+}
+
+fn screenshot_config(args: &RunArgs, file: &Path) -> ScreenshotConfig {
+    ScreenshotConfig {
+        dir: args.screenshot_dir.clone(),
+        name: file
+            .file_stem()
+            .map_or("chip8".into(), |stem| stem.to_string_lossy().into_owned()),
+        scale: args.scale,
+    }
+}
+
+fn asm(source: &Path, output: Option<PathBuf>) -> Result<(), String> {
+    let text = fs::read_to_string(source)
+        .map_err(|e| format!("could not read {}: {e}", source.display()))?;
+    let rom = asm::assemble(&text).map_err(|e| format!("{}: {e}", source.display()))?;
+    let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+    fs::write(&output, rom).map_err(|e| format!("could not write {}: {e}", output.display()))
+}
+
+fn info(rom: &[u8]) {
+    let info = romdb::lookup(rom);
+    println!("Size:     {} bytes", rom.len());
+    println!("SHA-1:    {}", romdb::sha1(rom));
+    if let Some(info) = &info {
+        println!("Title:    {}", info.full_title());
+    }
+    let platform = match info.as_ref().and_then(|i| i.platform) {
+        Some(platform) => {
+            println!("Platform: {platform} (from the ROM database)");
+            platform
+        }
+        None => {
+            let platform = analysis::detect_platform(rom);
+            println!("Platform: {platform} (detected)");
+            platform
+        }
+    };
+    let report = analysis::inspect(rom, platform);
+    println!(
+        "Self-modifying code: {}",
+        match report.self_modifying {
+            SelfModifying::No => "no",
+            SelfModifying::Maybe => "maybe, stores through an untracked I",
+            SelfModifying::Yes => "yes",
+        }
+    );
+    println!();
+    println!("Opcodes:");
+    let mut histogram: Vec<_> = report.histogram.into_iter().collect();
+    histogram.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    for (pattern, count) in histogram {
+        println!("  {pattern:<8}{count:>5}");
+    }
+    println!();
+    println!("Call targets:");
+    if report.call_targets.is_empty() {
+        println!("  none");
+    }
+    for target in report.call_targets {
+        println!("  {target:03X}");
+    }
+    println!();
+    println!("Sprite data:");
+    if report.sprite_regions.is_empty() {
+        println!("  none found");
+    }
+    for region in report.sprite_regions {
+        println!(
+            "  {:03X}-{:03X} ({} bytes)",
+            region.start,
+            region.end - 1,
+            region.end - region.start
+        );
+    }
+    println!();
+    println!("Unimplemented instructions:");
+    if report.unimplemented.is_empty() {
+        println!("  none");
+    }
+    for (instruction, addresses) in report.unimplemented {
+        let addresses: Vec<String> = addresses.iter().map(|a| format!("{a:03X}")).collect();
+        println!("  {instruction:04X} at {}", addresses.join(", "));
+    }
+}
+
+fn test(
+    rom: &Path,
+    frontend: Box<Headless>,
+    expect: Option<PathBuf>,
+    machine: MachineArgs,
+) -> Result<(), String> {
+    let rom = read_rom(rom)?;
+    let audio = audio::open_sink(&AudioBackend::Null, AudioConfig::default())?;
+    let mut vm = Vm::new(
+        rom,
+        frontend,
+        audio,
+        Options {
+            seed: Some(machine.seed.unwrap_or(0)),
+            ..machine.options()?
+        },
+    )?;
+    vm.start()?;
+    let screen = vm.screen_text();
+    print!("{screen}");
+    if let Some(expect) = expect {
+        let expected = fs::read_to_string(&expect)
+            .map_err(|e| format!("could not read {}: {e}", expect.display()))?;
+        if expected.trim_end() != screen.trim_end() {
+            return Err(format!("screen differs from {}", expect.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Runs `rom` under each quirk profile, failing if any check fails or no
+/// results can be read.
+fn test_profiles(
+    rom: &Path,
+    frontend: impl Fn() -> Box<Headless>,
+    machine: MachineArgs,
+) -> Result<(), String> {
+    let rom = read_rom(rom)?;
+    let mut failed = false;
+    for (platform, quirks) in conformance::profiles() {
+        let audio = audio::open_sink(&AudioBackend::Null, AudioConfig::default())?;
+        let mut vm = Vm::new(
+            rom.clone(),
+            frontend(),
+            audio,
+            Options {
+                platform: Some(platform),
+                quirks: Some(quirks),
+                seed: Some(machine.seed.unwrap_or(0)),
+                ..machine.options()?
+            },
+        )?;
+        vm.start()?;
+        let results = conformance::results(&vm.screen_text());
+        if results.is_empty() || results.contains('?') {
+            println!("{platform} ({quirks}): no results read");
+            failed = true;
+        } else {
+            println!("{platform} ({quirks}): {results}");
+            failed |= results.contains('✗');
+        }
+    }
+    if failed {
+        return Err("some profiles failed".to_string());
+    }
+    Ok(())
+}
+
+fn diff(
+    rom: &Path,
+    frames: u64,
+    keys: &KeySchedule,
+    machine: MachineArgs,
+    other: OtherMachineArgs,
+) -> Result<(), String> {
+    let rom = read_rom(rom)?;
+    let options = || {
+        Ok::<_, String>(Options {
+            seed: Some(machine.seed.unwrap_or(0)),
+            ..machine.options()?
+        })
+    };
+    let vm = |options| {
+        Vm::new(
+            rom.clone(),
+            Box::new(Headless::new(None)),
+            Box::new(NullSink),
+            options,
+        )
+    };
+    let mut a = vm(options()?)?;
+    let options = options()?;
+    let font = match (&other.other_font_file, other.other_font) {
+        (Some(path), _) => font::load(path)?,
+        (None, Some(font)) => *font.font(),
+        (None, None) => options.font,
+    };
+    let mut b = vm(Options {
+        platform: other.other_platform.or(machine.platform),
+        speed: other.other_speed.or(machine.speed),
+        quirks: other.other_quirks.or(machine.quirks),
+        rng: other.other_rng.unwrap_or(machine.rng),
+        engine: other.other_engine.unwrap_or(machine.engine),
+        font,
+        font_address: other.other_font_address.unwrap_or(machine.font_address),
+        ..options
+    })?;
+    match lockstep::run(&mut a, &mut b, frames, |frame| keys.keypad(frame))? {
+        Some(divergence) => {
+            print!("{divergence}");
+            Err("the machines diverged".to_string())
+        }
+        None => {
+            println!("No difference in {frames} frames");
+            Ok(())
+        }
+    }
+}
+
+/// Parses an address given in hex with a `0x` prefix, or in decimal.
+fn parse_address(s: &str) -> Result<u16, String> {
+    let address = match s.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    address.map_err(|_| format!("invalid address `{s}`"))
+}
+
+fn parse_frequency(s: &str) -> Result<f32, String> {
+    match s.parse() {
+        Ok(frequency) if frequency > 0.0 && f32::is_finite(frequency) => Ok(frequency),
+        _ => Err(format!(
+            "invalid frequency `{s}`, expected a number of Hz above 0"
+        )),
+    }
+}
+
+fn parse_volume(s: &str) -> Result<f32, String> {
+    match s.parse() {
+        Ok(volume) if (0.0..=1.0).contains(&volume) => Ok(volume),
+        _ => Err(format!("invalid volume `{s}`, expected 0.0 to 1.0")),
+    }
+}
+
+fn read_rom(file: &Path) -> Result<Vec<u8>, String> {
+    if file.is_dir() {
+        return Err(format!(
+            "{} is a directory, expected a ROM file",
+            file.display()
+        ));
+    }
+    fs::read(file).map_err(|e| format!("could not read {}: {e}", file.display()))
 }
@@ -0,0 +1,156 @@
+use std::{fmt, str::FromStr};
+
+/// The interpreter a ROM was written for. Each one implies a set of quirks
+/// and a typical speed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Platform {
+    /// The original COSMAC VIP interpreter.
+    Vip,
+    /// The COSMAC VIP two-page 64x64 mode, for ROMs starting with `1260`.
+    VipHires,
+    /// CHIP-48 on the HP-48 calculators.
+    Chip48,
+    SuperChip,
+    XoChip,
+}
+
+/// Behaviours that differ between CHIP-8 interpreters.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Quirks {
+    /// `8XY1`, `8XY2` and `8XY3` reset VF to 0.
+    pub vf_reset: bool,
+    /// `FX55` and `FX65` leave I pointing after the last register accessed.
+    pub increment_i: bool,
+    /// `DXYN` waits for the next frame, so at most one sprite is drawn per
+    /// frame.
+    pub display_wait: bool,
+    /// Sprites are clipped at the screen edges instead of wrapping around.
+    pub clip_sprites: bool,
+    /// `8XY6` and `8XYE` shift VX in place instead of shifting VY into VX.
+    pub shift_vx: bool,
+    /// `BXNN` jumps to XNN + VX instead of NNN + V0.
+    pub jump_vx: bool,
+}
+
+impl Platform {
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::Vip | Platform::VipHires => Quirks {
+                vf_reset: true,
+                increment_i: true,
+                display_wait: true,
+                clip_sprites: true,
+                shift_vx: false,
+                jump_vx: false,
+            },
+            Platform::Chip48 | Platform::SuperChip => Quirks {
+                vf_reset: false,
+                increment_i: false,
+                display_wait: false,
+                clip_sprites: true,
+                shift_vx: true,
+                jump_vx: true,
+            },
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                increment_i: true,
+                display_wait: false,
+                clip_sprites: false,
+                shift_vx: false,
+                jump_vx: false,
+            },
+        }
+    }
+    /// Instructions executed per 60 Hz frame.
+    pub fn speed(self) -> u32 {
+        match self {
+            Platform::Vip | Platform::VipHires => 15,
+            Platform::Chip48 | Platform::SuperChip => 30,
+            Platform::XoChip => 200,
+        }
+    }
+    pub fn memory_size(self) -> usize {
+        match self {
+            Platform::XoChip => 0x10000,
+            _ => 0x1000,
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Platform, String> {
+        match s {
+            "vip" | "chip8" => Ok(Platform::Vip),
+            "vip-hires" => Ok(Platform::VipHires),
+            "chip48" => Ok(Platform::Chip48),
+            "schip" | "superchip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(format!(
+                "unknown platform `{s}`, expected vip, vip-hires, chip48, schip or xochip"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Platform::Vip => "vip",
+            Platform::VipHires => "vip-hires",
+            Platform::Chip48 => "chip48",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for Quirks {
+    /// The quirks enabled, in the form `from_str` parses.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = [
+            (self.vf_reset, "vf-reset"),
+            (self.increment_i, "increment-i"),
+            (self.display_wait, "display-wait"),
+            (self.clip_sprites, "clip"),
+            (self.shift_vx, "shift"),
+            (self.jump_vx, "jump"),
+        ]
+        .into_iter()
+        .filter_map(|(on, name)| on.then_some(name))
+        .collect();
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join(","))
+        }
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = String;
+
+    /// Parses a comma or space separated list of the quirks to enable:
+    /// `vf-reset`, `increment-i`, `display-wait`, `clip`, `shift` and `jump`.
+    /// `none` enables none of them.
+    fn from_str(s: &str) -> Result<Quirks, String> {
+        let mut quirks = Quirks::default();
+        for name in s.split([',', ' ']).filter(|n| !n.is_empty()) {
+            match name {
+                "vf-reset" => quirks.vf_reset = true,
+                "increment-i" => quirks.increment_i = true,
+                "display-wait" => quirks.display_wait = true,
+                "clip" => quirks.clip_sprites = true,
+                "shift" => quirks.shift_vx = true,
+                "jump" => quirks.jump_vx = true,
+                "none" => {}
+                _ => return Err(format!("unknown quirk `{name}`")),
+            }
+        }
+        Ok(quirks)
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use gif::{Encoder, Frame, Repeat};
+
+use crate::{frontend::Palette, screenshot};
+
+// GIF delays are in hundredths of a second, and most viewers slow down
+// anything shorter than 2.
+const MIN_GIF_DELAY: f64 = 2.0;
+const FRAME_DURATION: f64 = 100.0 / 60.0;
+
+/// What `Recorder` writes and where.
+pub struct RecordConfig {
+    /// An animated GIF if it ends in `.gif`, raw RGB frames otherwise, `-`
+    /// for raw frames on stdout.
+    pub path: PathBuf,
+    pub scale: usize,
+}
+
+/// Records the display once per emulated frame.
+///
+/// Raw output is 24 bit RGB frames back to back at 60 frames per second,
+/// ready for e.g. `ffmpeg -f rawvideo -pixel_format rgb24 -video_size
+/// <width>x<height> -framerate 60 -i <file> out.mp4`.
+pub struct Recorder {
+    output: Output,
+    path: PathBuf,
+    palette: Palette,
+    scale: usize,
+}
+
+enum Output {
+    /// Created on the first frame, once the size is known.
+    Gif(Option<GifOutput>),
+    Raw(Box<dyn Write>),
+}
+
+struct GifOutput {
+    encoder: Encoder<BufWriter<File>>,
+    width: usize,
+    height: usize,
+    /// The frame shown since `shown_since`, not written until it changes
+    /// so unchanged frames make up a single longer GIF frame.
+    pending: Option<Vec<u8>>,
+    shown_since: f64,
+    time: f64,
+}
+
+impl Recorder {
+    pub fn new(config: &RecordConfig, palette: Palette) -> Result<Recorder, String> {
+        let path = &config.path;
+        let output = if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("gif"))
+        {
+            Output::Gif(None)
+        } else if path == Path::new("-") {
+            Output::Raw(Box::new(BufWriter::new(io::stdout())))
+        } else {
+            let file = File::create(path)
+                .map_err(|e| format!("could not create {}: {e}", path.display()))?;
+            Output::Raw(Box::new(BufWriter::new(file)))
+        };
+        Ok(Recorder {
+            output,
+            path: path.clone(),
+            palette,
+            scale: config.scale.max(1),
+        })
+    }
+    /// Adds a frame, `screen` holding one byte per pixel.
+    pub fn frame(&mut self, screen: &[&[u8]]) -> Result<(), String> {
+        let width = screen.first().map_or(0, |row| row.len()) * self.scale;
+        let height = screen.len() * self.scale;
+        match &mut self.output {
+            Output::Gif(gif) => {
+                let pixels = screenshot::scale(screen, self.scale, |pixel| [pixel]);
+                let gif = match gif {
+                    Some(gif) => gif,
+                    None => gif.insert(GifOutput::create(&self.path, self.palette, width, height)?),
+                };
+                if gif.width != width || gif.height != height {
+                    return Err("the resolution changed while recording".to_string());
+                }
+                let result = gif.add(pixels);
+                gif.time += FRAME_DURATION;
+                result.map_err(|e| format!("{}: {e}", self.path.display()))
+            }
+            Output::Raw(writer) => {
+                let palette = self.palette;
+                let rgb = screenshot::scale(screen, self.scale, |pixel| {
+                    if pixel == 1 {
+                        palette.foreground
+                    } else {
+                        palette.background
+                    }
+                });
+                writer
+                    .write_all(&rgb)
+                    .map_err(|e| format!("{}: {e}", self.path.display()))
+            }
+        }
+    }
+    /// Writes out buffered frames.
+    pub fn finish(mut self) -> Result<(), String> {
+        let result = match &mut self.output {
+            Output::Gif(Some(gif)) => gif.write_pending(gif.time),
+            Output::Gif(None) => Ok(()),
+            Output::Raw(writer) => writer.flush().map_err(|e| e.to_string()),
+        };
+        result.map_err(|e| format!("{}: {e}", self.path.display()))
+    }
+}
+
+impl GifOutput {
+    fn create(
+        path: &Path,
+        palette: Palette,
+        width: usize,
+        height: usize,
+    ) -> Result<GifOutput, String> {
+        let file =
+            File::create(path).map_err(|e| format!("could not create {}: {e}", path.display()))?;
+        let palette = [palette.background, palette.foreground].concat();
+        let encoder = Encoder::new(BufWriter::new(file), width as u16, height as u16, &palette)
+            .and_then(|mut encoder| encoder.set_repeat(Repeat::Infinite).map(|_| encoder))
+            .map_err(|e| format!("could not write {}: {e}", path.display()))?;
+        Ok(GifOutput {
+            encoder,
+            width,
+            height,
+            pending: None,
+            shown_since: 0.0,
+            time: 0.0,
+        })
+    }
+    /// Adds the frame shown from `self.time`.
+    fn add(&mut self, pixels: Vec<u8>) -> Result<(), String> {
+        match &self.pending {
+            Some(pending) if *pending == pixels => {}
+            Some(_) if self.time - self.shown_since < MIN_GIF_DELAY => {
+                // The pending frame is too short to show, drop it.
+                self.pending = Some(pixels);
+            }
+            _ => {
+                self.write_pending(self.time)?;
+                self.pending = Some(pixels);
+                self.shown_since = self.time;
+            }
+        }
+        Ok(())
+    }
+    fn write_pending(&mut self, until: f64) -> Result<(), String> {
+        let Some(pending) = &self.pending else {
+            return Ok(());
+        };
+        // Rounding the end points rather than the length keeps the
+        // rounding errors from adding up.
+        let delay = until.round() - self.shown_since.round();
+        let frame = Frame {
+            width: self.width as u16,
+            height: self.height as u16,
+            delay: delay.max(MIN_GIF_DELAY) as u16,
+            buffer: Cow::Borrowed(pending),
+            ..Frame::default()
+        };
+        self.encoder.write_frame(&frame).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,117 @@
+use std::str::FromStr;
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+#[cfg(test)]
+mod tests;
+
+/// Where `CXNN` gets its random numbers.
+pub trait Rng {
+    fn next_byte(&mut self) -> u8;
+    /// Called once per 60 Hz frame, for generators that depend on timing.
+    fn tick(&mut self) {}
+}
+
+#[derive(Clone, Copy, Default)]
+pub enum RngMode {
+    /// A seeded generator, the same numbers for the same seed.
+    #[default]
+    Seeded,
+    /// The COSMAC VIP interpreter's routine, see `VipRng`.
+    Vip,
+}
+
+impl FromStr for RngMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RngMode, String> {
+        match s {
+            "seeded" => Ok(RngMode::Seeded),
+            "vip" => Ok(RngMode::Vip),
+            _ => Err(format!("unknown RNG `{s}`, expected seeded or vip")),
+        }
+    }
+}
+
+/// A generator for `mode` starting from `seed`.
+pub fn new(mode: RngMode, seed: u64) -> Box<dyn Rng> {
+    match mode {
+        RngMode::Seeded => Box::new(SeededRng::new(seed)),
+        RngMode::Vip => Box::new(VipRng::new(seed)),
+    }
+}
+
+/// ChaCha8, whose output for a given seed doesn't change between versions of
+/// the crate.
+pub struct SeededRng(ChaCha8Rng);
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        SeededRng(ChaCha8Rng::seed_from_u64(seed))
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_byte(&mut self) -> u8 {
+        self.0.next_u32() as u8
+    }
+}
+
+/// The COSMAC VIP interpreter's `CXNN` routine. Its seed is the VIP's R9
+/// register, which the VIP's interrupt routine counts up every frame and
+/// `CXNN` counts up again before using its low byte to pick a byte of the
+/// interpreter's own code. That byte is added to the high byte, and the sum
+/// added again to itself rotated right through the carry, giving the new
+/// high byte. The numbers depend on how many frames passed between calls, and
+/// games calling `CXNN` in a tight loop get the same poor spread they got on
+/// the VIP.
+pub struct VipRng {
+    seed: u16,
+}
+
+impl VipRng {
+    pub fn new(seed: u64) -> VipRng {
+        VipRng { seed: seed as u16 }
+    }
+}
+
+impl Rng for VipRng {
+    fn next_byte(&mut self) -> u8 {
+        // INC R9; GLO R9; PLO RE; GHI R3; PHI RE
+        self.seed = self.seed.wrapping_add(1);
+        let [high, low] = self.seed.to_be_bytes();
+        // GHI R9; SEX RE; ADD; STR R6
+        let (sum, carry) = INTERPRETER_PAGE[low as usize].overflowing_add(high);
+        // SHRC; SEX R6; ADD; PHI R9
+        let high = sum.wrapping_add(sum >> 1 | (carry as u8) << 7);
+        self.seed = u16::from_be_bytes([high, low]);
+        high
+    }
+    fn tick(&mut self) {
+        // INC R9 in the interrupt routine.
+        self.seed = self.seed.wrapping_add(1);
+    }
+}
+
+/// The second page of the VIP's CHIP-8 interpreter, 0x100 to 0x1FF, where
+/// its instruction routines live. `CXNN` reads it as a table of noise.
+#[rustfmt::skip]
+const INTERPRETER_PAGE: [u8; 256] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x45, 0xA3, 0x98, 0x56, 0xD4, 0xF8, 0x81, 0xBC, 0xF8, 0x95, 0xAC,
+    0x22, 0xDC, 0x12, 0x56, 0xD4, 0x06, 0xB8, 0xD4, 0x06, 0xA8, 0xD4, 0x64, 0x0A, 0x01, 0xE6, 0x8A,
+    0xF4, 0xAA, 0x3B, 0x28, 0x9A, 0xFC, 0x01, 0xBA, 0xD4, 0xF8, 0x81, 0xBA, 0x06, 0xFA, 0x0F, 0xAA,
+    0x0A, 0xAA, 0xD4, 0xE6, 0x06, 0xBF, 0x93, 0xBE, 0xF8, 0x1B, 0xAE, 0x2A, 0x1A, 0xF8, 0x00, 0x5A,
+    0x0E, 0xF5, 0x3B, 0x4B, 0x56, 0x0A, 0xFC, 0x01, 0x5A, 0x30, 0x40, 0x4E, 0xF6, 0x3B, 0x3C, 0x9F,
+    0x56, 0x2A, 0x2A, 0xD4, 0x00, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x07, 0x5A, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x5B, 0x12, 0xD4, 0x22, 0x86, 0x52, 0xF8, 0xF0, 0xA7, 0x0A, 0x57, 0x87, 0xF3, 0x17,
+    0x1A, 0x3A, 0x6B, 0x12, 0xD4, 0x15, 0x85, 0x22, 0x73, 0x95, 0x52, 0x25, 0x45, 0xA5, 0x86, 0xFA,
+    0x0F, 0xB5, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x82, 0x15, 0x15, 0xD4, 0x45, 0xE6, 0xF3, 0x3A, 0x88,
+    0xD4, 0x45, 0x07, 0x30, 0x8C, 0x45, 0x07, 0x30, 0x84, 0xE6, 0x62, 0x26, 0x45, 0xA3, 0x36, 0x88,
+    0xD4, 0x3E, 0x88, 0xD4, 0xF8, 0xF0, 0xA7, 0xE7, 0x45, 0xF4, 0xA5, 0x86, 0xFA, 0x0F, 0x3B, 0xB2,
+    0xFC, 0x01, 0xB5, 0xD4, 0x45, 0x56, 0xD4, 0x45, 0xE6, 0xF4, 0x56, 0xD4, 0x45, 0xFA, 0x0F, 0x3A,
+    0xC4, 0x07, 0x56, 0xD4, 0xAF, 0x22, 0xF8, 0xD3, 0x73, 0x8F, 0xF9, 0xF0, 0x52, 0xE6, 0x07, 0xD2,
+    0x56, 0xF8, 0xFF, 0xA6, 0xF8, 0x00, 0x7E, 0x56, 0xD4, 0x19, 0x89, 0xAE, 0x93, 0xBE, 0x99, 0xEE,
+    0xF4, 0x56, 0x76, 0xE6, 0xF4, 0xB9, 0x56, 0x45, 0xF2, 0x56, 0xD4, 0x45, 0xAA, 0x86, 0xFA, 0x0F,
+    0xBA, 0xD4, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
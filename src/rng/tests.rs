@@ -0,0 +1,36 @@
+use super::*;
+
+fn bytes(rng: &mut dyn Rng, count: usize) -> Vec<u8> {
+    (0..count).map(|_| rng.next_byte()).collect()
+}
+
+#[test]
+fn same_seed_gives_the_same_numbers() {
+    for mode in [RngMode::Seeded, RngMode::Vip] {
+        let first = bytes(&mut *new(mode, 42), 64);
+        assert_eq!(first, bytes(&mut *new(mode, 42), 64));
+    }
+    assert_ne!(
+        bytes(&mut *new(RngMode::Seeded, 1), 64),
+        bytes(&mut *new(RngMode::Seeded, 2), 64)
+    );
+}
+
+#[test]
+fn vip_rng_follows_the_interpreter_routine() {
+    // R9 = 0x0004. The first call reads 0x105 (0x45): 0x45 + 0x00 = 0x45,
+    // plus 0x45 rotated right is 0x67. The second reads 0x106 (0xA3):
+    // 0xA3 + 0x67 = 0x0A with a carry, plus 0x85 is 0x8F. The third reads
+    // 0x107 (0x98): 0x98 + 0x8F = 0x27 with a carry, plus 0x93 is 0xBA.
+    assert_eq!(bytes(&mut VipRng::new(4), 3), [0x67, 0x8F, 0xBA]);
+}
+
+#[test]
+fn vip_rng_counts_frames() {
+    let mut rng = VipRng::new(4);
+    rng.tick();
+    // Reads 0x106 (0xA3) with a high byte of 0: 0xA3 + 0x51 = 0xF4.
+    assert_eq!(rng.next_byte(), 0xF4);
+    // R9 keeps the number in its high byte.
+    assert_eq!(rng.seed, 0xF406);
+}
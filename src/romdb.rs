@@ -0,0 +1,98 @@
+use sha1_smol::Sha1;
+
+use crate::{
+    frontend::Palette,
+    platform::{Platform, Quirks},
+};
+
+#[cfg(test)]
+mod tests;
+
+const DATABASE: &str = include_str!("romdb.ini");
+
+/// What the bundled database knows about a ROM. Settings left as `None`
+/// fall back to the platform's defaults.
+#[derive(Default)]
+pub struct RomInfo {
+    pub title: String,
+    pub author: Option<String>,
+    pub year: Option<u16>,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub speed: Option<u32>,
+    pub palette: Option<Palette>,
+    pub key_hints: Option<String>,
+}
+
+impl RomInfo {
+    /// The title followed by the author and year when known, e.g.
+    /// `Tetris (Fran Dachille, 1991)`.
+    pub fn full_title(&self) -> String {
+        let credits: Vec<String> = self
+            .author
+            .iter()
+            .cloned()
+            .chain(self.year.map(|y| y.to_string()))
+            .collect();
+        if credits.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} ({})", self.title, credits.join(", "))
+        }
+    }
+}
+
+pub fn sha1(rom: &[u8]) -> String {
+    Sha1::from(rom).digest().to_string()
+}
+
+pub fn lookup(rom: &[u8]) -> Option<RomInfo> {
+    let section = format!("[{}]", sha1(rom));
+    let mut lines = DATABASE
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .skip_while(|l| *l != section)
+        .skip(1)
+        .take_while(|l| !l.starts_with('['))
+        .peekable();
+    lines.peek()?;
+    let mut info = RomInfo::default();
+    for line in lines {
+        let Some((key, value)) = line.split_once('=') else {
+            eprintln!("Ignoring malformed ROM database line `{line}`");
+            continue;
+        };
+        let value = value.trim();
+        let result = match key.trim() {
+            "title" => {
+                info.title = value.to_string();
+                Ok(())
+            }
+            "author" => {
+                info.author = Some(value.to_string());
+                Ok(())
+            }
+            "year" => value
+                .parse()
+                .map(|year| info.year = Some(year))
+                .map_err(|e| e.to_string()),
+            "platform" => value.parse().map(|p| info.platform = Some(p)),
+            "quirks" => value.parse().map(|q| info.quirks = Some(q)),
+            "speed" => value
+                .parse()
+                .map(|speed| info.speed = Some(speed))
+                .map_err(|e| e.to_string()),
+            "palette" => value.parse().map(|p| info.palette = Some(p)),
+            "keys" => {
+                info.key_hints = Some(value.to_string());
+                Ok(())
+            }
+            key => Err(format!("unknown key `{key}`")),
+        };
+        if let Err(e) = result {
+            eprintln!("Ignoring ROM database line `{line}`: {e}");
+        }
+    }
+    Some(info)
+}
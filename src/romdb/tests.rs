@@ -0,0 +1,42 @@
+use super::*;
+
+const TETRIS: &[u8] = include_bytes!("../../examples/Tetris [Fran Dachille, 1991].ch8");
+
+#[test]
+fn sha1_is_lowercase_hex() {
+    assert_eq!(sha1(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(sha1(TETRIS), "5f518084744bf3cb8733f6e5454dfd1634320563");
+}
+
+#[test]
+fn finds_a_known_rom() {
+    let info = lookup(TETRIS).unwrap();
+    assert_eq!(info.full_title(), "Tetris (Fran Dachille, 1991)");
+    assert_eq!(info.platform, Some(Platform::Chip48));
+    assert_eq!(info.speed, Some(10));
+    let palette = info.palette.unwrap();
+    assert_eq!(
+        (palette.background, palette.foreground),
+        ([0x10, 0x10, 0x20], [0xFF, 0xCC, 0x33])
+    );
+    assert_eq!(
+        info.key_hints.as_deref(),
+        Some("4 rotate, 5 left, 6 right, 1 drop")
+    );
+    assert!(info.quirks.is_none());
+}
+
+#[test]
+fn misses_an_unknown_rom() {
+    assert!(lookup(&[0x12, 0x00]).is_none());
+    assert!(lookup(&[]).is_none());
+}
+
+#[test]
+fn full_title_leaves_out_unknown_credits() {
+    let info = RomInfo {
+        title: "Pong".to_string(),
+        ..RomInfo::default()
+    };
+    assert_eq!(info.full_title(), "Pong");
+}
@@ -1,37 +1,54 @@
-use std::sync::mpsc;
-use std::{process, thread};
+use std::{collections::VecDeque, process};
 
 use sdl2::event::Event;
 use sdl2::keyboard::{Keycode, Scancode};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
+use sdl2::render::{BlendMode, Canvas};
 use sdl2::video::Window;
 use sdl2::EventPump;
 
+use crate::{
+    frontend::{Frontend, MainLoopAction, Palette},
+    keymap::Keymap,
+    keypad::Keypad,
+    text::{self, CHAR_WIDTH, LINE_HEIGHT},
+};
+
 pub struct Screen {
     canvas: Canvas<Window>,
     event_pump: EventPump,
-}
-
-pub enum MainLoopAction {
-    Interrupt,
-    Continue,
+    /// Events polled but not handled yet, left after a hotkey ended `draw`
+    /// early.
+    pending: VecDeque<Event>,
+    help_text: Option<String>,
+    key_hints: Option<String>,
+    status: Option<String>,
+    palette: Palette,
+    scale: usize,
+    keymap: Keymap,
+    last_frame: Vec<Rect>,
 }
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
-pub const SCALING_FACTOR: usize = 9;
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
+pub const DEFAULT_SCALE: usize = 9;
+const HELP_TEXT_SCALE: u32 = 2;
+const STATUS_TEXT_SCALE: u32 = 2;
+const CONTROLS: &str = "F1: help  F2: reset  F3: pause  F4: next frame  \
+F5: save state  F6: slow motion  F9: load state  F12: screenshot  Tab: fast-forward  \
+Esc: quit";
 
 impl Screen {
-    pub fn new() -> Screen {
+    pub fn new(scale: usize, keymap: Keymap) -> Screen {
         let sdl_context = sdl2::init().unwrap();
         let video_subsystem = sdl_context.video().unwrap();
         let window = video_subsystem
             .window(
                 "chip8",
-                (SCREEN_WIDTH * SCALING_FACTOR) as u32,
-                (SCREEN_HEIGHT * SCALING_FACTOR) as u32,
+                (SCREEN_WIDTH * scale) as u32,
+                (SCREEN_HEIGHT * scale) as u32,
             )
             .position_centered()
             .build()
@@ -40,45 +57,179 @@ impl Screen {
         canvas.set_draw_color(Color::RGB(0, 0, 0));
         canvas.clear();
         canvas.present();
-        // canvas.set_scale(SCALING_FACTOR as f32, SCALING_FACTOR as f32);
         Screen {
             canvas,
             event_pump: sdl_context.event_pump().unwrap(),
+            pending: VecDeque::new(),
+            help_text: None,
+            key_hints: None,
+            status: None,
+            palette: Palette::default(),
+            scale,
+            keymap,
+            last_frame: vec![],
+        }
+    }
+    /// Sets the instructions shown by the F1 help overlay.
+    pub fn set_help_text(&mut self, help_text: Option<String>) {
+        self.help_text = help_text;
+    }
+    /// Shows the help text over the game until F1 or Escape is pressed. The
+    /// caller is blocked meanwhile, which pauses emulation.
+    fn show_help(&mut self) -> MainLoopAction {
+        let (width, height) = self.canvas.output_size().unwrap();
+        let line_height = LINE_HEIGHT * HELP_TEXT_SCALE;
+        let columns = (width / (CHAR_WIDTH * HELP_TEXT_SCALE)) as usize;
+        let rows = (height / line_height) as usize - 1;
+        let help_text = self
+            .help_text
+            .as_deref()
+            .unwrap_or("No instructions found for this ROM.");
+        let lines = text::wrap(
+            &match &self.key_hints {
+                Some(key_hints) => format!("Keys: {key_hints}\n\n{help_text}\n\n{CONTROLS}"),
+                None => format!("{help_text}\n\n{CONTROLS}"),
+            },
+            columns,
+        );
+        let max_scroll = lines.len().saturating_sub(rows);
+        let mut scroll = 0;
+        loop {
+            self.draw_last_frame();
+            self.canvas.set_blend_mode(BlendMode::Blend);
+            self.canvas.set_draw_color(Color::RGBA(0, 0, 0, 210));
+            self.canvas.fill_rect(None).unwrap();
+            self.canvas.set_blend_mode(BlendMode::None);
+            self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+            for (row, line) in lines.iter().skip(scroll).take(rows).enumerate() {
+                let y = (row as u32 * line_height + HELP_TEXT_SCALE) as i32;
+                text::draw_text(&mut self.canvas, 0, y, HELP_TEXT_SCALE, line).unwrap();
+            }
+            self.canvas.set_draw_color(Color::RGB(128, 128, 128));
+            let footer = "Up/Down: scroll  F1/Esc: close";
+            let y = (rows as u32 * line_height + HELP_TEXT_SCALE) as i32;
+            text::draw_text(&mut self.canvas, 0, y, HELP_TEXT_SCALE, footer).unwrap();
+            self.canvas.present();
+            match self.event_pump.wait_event() {
+                Event::Quit { .. } => return MainLoopAction::Interrupt,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => match keycode {
+                    Keycode::F1 | Keycode::Escape => break,
+                    Keycode::Up => scroll = scroll.saturating_sub(1),
+                    Keycode::Down => scroll = (scroll + 1).min(max_scroll),
+                    Keycode::PageUp => scroll = scroll.saturating_sub(rows),
+                    Keycode::PageDown => scroll = (scroll + rows).min(max_scroll),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        self.draw_last_frame();
+        self.canvas.present();
+        MainLoopAction::Continue
+    }
+    fn draw_last_frame(&mut self) {
+        self.canvas.set_draw_color(rgb(self.palette.background));
+        self.canvas.clear();
+        if !self.last_frame.is_empty() {
+            self.canvas.set_draw_color(rgb(self.palette.foreground));
+            self.canvas.fill_rects(&self.last_frame).unwrap();
         }
+        self.draw_status();
+    }
+    fn draw_status(&mut self) {
+        let Some(status) = &self.status else {
+            return;
+        };
+        let width = status.len() as u32 * CHAR_WIDTH * STATUS_TEXT_SCALE + STATUS_TEXT_SCALE;
+        let height = LINE_HEIGHT * STATUS_TEXT_SCALE;
+        self.canvas.set_draw_color(rgb(self.palette.background));
+        self.canvas
+            .fill_rect(Rect::new(0, 0, width, height))
+            .unwrap();
+        self.canvas.set_draw_color(rgb(self.palette.foreground));
+        let (x, y) = (STATUS_TEXT_SCALE as i32, STATUS_TEXT_SCALE as i32);
+        text::draw_text(&mut self.canvas, x, y, STATUS_TEXT_SCALE, status).unwrap();
+    }
+    pub fn canvas(&mut self) -> &mut Canvas<Window> {
+        &mut self.canvas
     }
-    pub fn draw(&mut self) -> MainLoopAction {
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    pub fn wait_event(&mut self) -> Event {
+        self.event_pump.wait_event()
+    }
+}
+
+impl Frontend for Screen {
+    fn draw(&mut self) -> MainLoopAction {
+        self.canvas.set_draw_color(rgb(self.palette.background));
         self.canvas.clear();
-        for event in self.event_pump.poll_iter() {
+        self.pending.extend(self.event_pump.poll_iter());
+        while let Some(event) = self.pending.pop_front() {
             match event {
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => return MainLoopAction::Interrupt,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F1),
+                    ..
+                } => {
+                    if let MainLoopAction::Interrupt = self.show_help() {
+                        return MainLoopAction::Interrupt;
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    ..
+                } => return MainLoopAction::Reset,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F3),
+                    repeat: false,
+                    ..
+                } => return MainLoopAction::TogglePause,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F4),
+                    ..
+                } => return MainLoopAction::FrameAdvance,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    ..
+                } => return MainLoopAction::SaveState,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    repeat: false,
+                    ..
+                } => return MainLoopAction::ToggleSlowMotion,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    ..
+                } => return MainLoopAction::LoadState,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => return MainLoopAction::Screenshot,
                 _ => {}
             }
         }
         MainLoopAction::Continue
     }
-    pub fn clear(&mut self) {
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        self.canvas.clear();
-        self.canvas.present();
-    }
-    pub fn draw_points(&mut self, points: &[(i32, i32)]) {
+    fn draw_points(&mut self, points: &[(i32, i32)]) {
         let sdl_rects: Vec<Rect> = points
             .iter()
             .map(|p| {
                 Rect::from((
-                    p.0 * SCALING_FACTOR as i32,
-                    p.1 * SCALING_FACTOR as i32,
-                    SCALING_FACTOR as u32,
-                    SCALING_FACTOR as u32,
+                    p.0 * self.scale as i32,
+                    p.1 * self.scale as i32,
+                    self.scale as u32,
+                    self.scale as u32,
                 ))
             })
             .collect();
-        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
+        self.canvas.set_draw_color(rgb(self.palette.foreground));
         match self.canvas.fill_rects(&sdl_rects[..]) {
             Ok(_) => {}
             Err(e) => {
@@ -86,72 +237,48 @@ impl Screen {
                 process::exit(1);
             }
         }
+        self.last_frame = sdl_rects;
+        self.draw_status();
         self.canvas.present();
     }
-    pub fn is_key_pressed(&mut self, key: u8) -> bool {
-        let scancode = match key {
-            1 => Scancode::Num1,
-            2 => Scancode::Num2,
-            3 => Scancode::Num3,
-            0xC => Scancode::Num4,
-            4 => Scancode::Q,
-            5 => Scancode::W,
-            6 => Scancode::E,
-            0xD => Scancode::R,
-            7 => Scancode::A,
-            8 => Scancode::S,
-            9 => Scancode::D,
-            0xE => Scancode::F,
-            0xA => Scancode::Z,
-            0 => Scancode::X,
-            0xB => Scancode::C,
-            0xF => Scancode::V,
-            _ => unreachable!(),
-        };
+    fn keypad(&mut self) -> Keypad {
+        let keyboard = self.event_pump.keyboard_state();
+        Keypad::from_fn(|key| {
+            // Keycodes of printable keys are their lowercase character.
+            Keycode::from_i32(self.keymap.char_for(key) as i32)
+                .and_then(Scancode::from_keycode)
+                .is_some_and(|scancode| keyboard.is_scancode_pressed(scancode))
+        })
+    }
+    fn set_title(&mut self, title: &str) {
+        // Only fails on titles containing a NUL byte.
+        let _ = self
+            .canvas
+            .window_mut()
+            .set_title(&format!("chip8 - {title}"));
+    }
+    fn set_resolution(&mut self, width: usize, height: usize) {
+        let window = self.canvas.window_mut();
+        if let Err(e) = window.set_size((width * self.scale) as u32, (height * self.scale) as u32) {
+            eprintln!("Error resizing window: {e}");
+        }
+    }
+    fn is_fast_forward_held(&mut self) -> bool {
         self.event_pump
             .keyboard_state()
-            .is_scancode_pressed(scancode)
+            .is_scancode_pressed(Scancode::Tab)
     }
-    pub fn wait_for_keypress(&mut self) -> Option<u8> {
-        loop {
-            match self.event_pump.wait_event() {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => return None,
-                Event::KeyDown {
-                    keycode: Some(keycode),
-                    ..
-                } => {
-                    if let Some(key) = keycode_to_u8(keycode) {
-                        return Some(key);
-                    }
-                }
-                _ => {}
-            }
-        }
+    fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+    fn set_key_hints(&mut self, key_hints: Option<String>) {
+        self.key_hints = key_hints;
     }
 }
 
-fn keycode_to_u8(keycode: Keycode) -> Option<u8> {
-    match keycode {
-        Keycode::Num1 => Some(1),
-        Keycode::Num2 => Some(2),
-        Keycode::Num3 => Some(3),
-        Keycode::Num4 => Some(0xC),
-        Keycode::Q => Some(4),
-        Keycode::W => Some(5),
-        Keycode::E => Some(6),
-        Keycode::R => Some(0xD),
-        Keycode::A => Some(7),
-        Keycode::S => Some(8),
-        Keycode::D => Some(9),
-        Keycode::F => Some(0xE),
-        Keycode::Z => Some(0xA),
-        Keycode::X => Some(0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-        _ => None,
-    }
+fn rgb([r, g, b]: [u8; 3]) -> Color {
+    Color::RGB(r, g, b)
 }
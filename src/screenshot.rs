@@ -0,0 +1,103 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::frontend::Palette;
+
+/// Where screenshots go and what they are called.
+pub struct ScreenshotConfig {
+    pub dir: PathBuf,
+    /// Prefix of the file names, usually the ROM's file name.
+    pub name: String,
+    /// Pixels per CHIP-8 pixel in the scaled screenshot.
+    pub scale: usize,
+}
+
+/// Writes `screen`, one byte per pixel, as `<name>-<timestamp>.png` at
+/// native size and `<name>-<timestamp>-<scale>x.png` scaled up. Returns the
+/// paths written.
+pub fn save(
+    config: &ScreenshotConfig,
+    screen: &[&[u8]],
+    palette: Palette,
+) -> Result<Vec<PathBuf>, String> {
+    let stem = format!("{}-{}", config.name, timestamp());
+    let native = config.dir.join(format!("{stem}.png"));
+    write_png(&native, screen, palette, 1)?;
+    let mut paths = vec![native];
+    if config.scale > 1 {
+        let scaled = config.dir.join(format!("{stem}-{}x.png", config.scale));
+        write_png(&scaled, screen, palette, config.scale)?;
+        paths.push(scaled);
+    }
+    Ok(paths)
+}
+
+fn write_png(path: &Path, screen: &[&[u8]], palette: Palette, scale: usize) -> Result<(), String> {
+    let width = screen.first().map_or(0, |row| row.len()) * scale;
+    let height = screen.len() * scale;
+    let data = self::scale(screen, scale, |pixel| {
+        if pixel == 1 {
+            palette.foreground
+        } else {
+            palette.background
+        }
+    });
+    let file =
+        File::create(path).map_err(|e| format!("could not create {}: {e}", path.display()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&data))
+        .map_err(|e| format!("could not write {}: {e}", path.display()))
+}
+
+/// Scales `screen` up, turning each pixel into the `N` bytes `color` gives.
+pub fn scale<const N: usize>(
+    screen: &[&[u8]],
+    scale: usize,
+    color: impl Fn(u8) -> [u8; N],
+) -> Vec<u8> {
+    let mut data = vec![];
+    for row in screen {
+        let line: Vec<u8> = row
+            .iter()
+            .flat_map(|&pixel| color(pixel).repeat(scale))
+            .collect();
+        for _ in 0..scale {
+            data.extend_from_slice(&line);
+        }
+    }
+    data
+}
+
+/// The current UTC time as `YYYYMMDD-HHMMSS`.
+pub fn timestamp() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    // Days since 1970-01-01 to a civil date, from Howard Hinnant's
+    // `civil_from_days`.
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
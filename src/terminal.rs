@@ -0,0 +1,273 @@
+use std::{
+    collections::HashMap,
+    io::{self, Stdout, Write},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, ClearType},
+};
+
+use crate::{
+    frontend::{Frontend, MainLoopAction, Palette},
+    keymap::Keymap,
+    keypad::Keypad,
+    screen::{SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+// Most terminals only report presses, repeated while the key is held, so a
+// key counts as held for a while after each one. Long enough to bridge the
+// usual delay before a held key starts repeating.
+const KEY_HOLD: Duration = Duration::from_millis(550);
+// Until the release is reported, on terminals that report releases.
+const KEY_HOLD_UNTIL_RELEASE: Duration = Duration::from_secs(3600);
+
+#[derive(Clone, Copy)]
+pub enum TerminalMode {
+    /// Two pixels per character with `▀`, `▄` and `█`.
+    HalfBlock,
+    /// Eight pixels per character with braille patterns, for small
+    /// terminals.
+    Braille,
+}
+
+impl FromStr for TerminalMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TerminalMode, String> {
+        match s {
+            "half" | "halfblock" => Ok(TerminalMode::HalfBlock),
+            "braille" => Ok(TerminalMode::Braille),
+            _ => Err(format!(
+                "unknown terminal mode `{s}`, expected half or braille"
+            )),
+        }
+    }
+}
+
+/// Draws in the terminal and reads keys from stdin in raw mode, so the
+/// emulator works over SSH.
+pub struct Terminal {
+    stdout: Stdout,
+    mode: TerminalMode,
+    keymap: Keymap,
+    palette: Palette,
+    width: usize,
+    height: usize,
+    status: Option<String>,
+    reports_releases: bool,
+    /// Keys held down, with when they count as released.
+    held: HashMap<KeyCode, Instant>,
+}
+
+impl Terminal {
+    pub fn new(mode: TerminalMode, keymap: Keymap) -> Result<Terminal, String> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode().map_err(|e| format!("could not set up the terminal: {e}"))?;
+        let reports_releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        let mut setup = execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide);
+        if reports_releases {
+            setup = setup.and_then(|_| {
+                execute!(
+                    stdout,
+                    PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+                )
+            });
+        }
+        let terminal = Terminal {
+            stdout,
+            mode,
+            keymap,
+            palette: Palette::default(),
+            width: SCREEN_WIDTH,
+            height: SCREEN_HEIGHT,
+            status: None,
+            reports_releases,
+            held: HashMap::new(),
+        };
+        // Dropping `terminal` restores the terminal if this failed.
+        setup.map_err(|e| format!("could not set up the terminal: {e}"))?;
+        Ok(terminal)
+    }
+    /// Updates the held keys, returning the action the event asks for.
+    fn handle_event(&mut self, event: Event) -> MainLoopAction {
+        let Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind,
+            ..
+        }) = event
+        else {
+            if let Event::Resize(..) = event {
+                let _ = execute!(self.stdout, terminal::Clear(ClearType::All));
+            }
+            return MainLoopAction::Continue;
+        };
+        let code = match code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+        if kind == KeyEventKind::Release {
+            self.held.remove(&code);
+            return MainLoopAction::Continue;
+        }
+        let hold = if self.reports_releases {
+            KEY_HOLD_UNTIL_RELEASE
+        } else {
+            KEY_HOLD
+        };
+        self.held.insert(code, Instant::now() + hold);
+        if kind == KeyEventKind::Repeat {
+            return MainLoopAction::Continue;
+        }
+        match code {
+            KeyCode::Esc => MainLoopAction::Interrupt,
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => {
+                MainLoopAction::Interrupt
+            }
+            KeyCode::F(2) => MainLoopAction::Reset,
+            KeyCode::F(3) => MainLoopAction::TogglePause,
+            KeyCode::F(4) => MainLoopAction::FrameAdvance,
+            KeyCode::F(5) => MainLoopAction::SaveState,
+            KeyCode::F(6) => MainLoopAction::ToggleSlowMotion,
+            KeyCode::F(9) => MainLoopAction::LoadState,
+            KeyCode::F(12) => MainLoopAction::Screenshot,
+            _ => MainLoopAction::Continue,
+        }
+    }
+    fn is_held(&self, code: KeyCode) -> bool {
+        self.held
+            .get(&code)
+            .is_some_and(|&until| until > Instant::now())
+    }
+    fn render(&self, pixels: &[Vec<bool>]) -> String {
+        let pixel = |x: usize, y: usize| y < self.height && pixels[y][x];
+        let mut lines = vec![];
+        match self.mode {
+            TerminalMode::HalfBlock => {
+                for y in (0..self.height).step_by(2) {
+                    lines.push(
+                        (0..self.width)
+                            .map(|x| match (pixel(x, y), pixel(x, y + 1)) {
+                                (true, true) => '█',
+                                (true, false) => '▀',
+                                (false, true) => '▄',
+                                (false, false) => ' ',
+                            })
+                            .collect::<String>(),
+                    );
+                }
+            }
+            TerminalMode::Braille => {
+                // Dot bits of a braille pattern, by row and column.
+                const DOTS: [[u32; 2]; 4] =
+                    [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+                for y in (0..self.height).step_by(4) {
+                    lines.push(
+                        (0..self.width)
+                            .step_by(2)
+                            .map(|x| {
+                                let mut bits = 0;
+                                for (dy, row) in DOTS.iter().enumerate() {
+                                    for (dx, bit) in row.iter().enumerate() {
+                                        if x + dx < self.width && pixel(x + dx, y + dy) {
+                                            bits |= bit;
+                                        }
+                                    }
+                                }
+                                char::from_u32(0x2800 + bits).unwrap()
+                            })
+                            .collect::<String>(),
+                    );
+                }
+            }
+        }
+        // Raw mode doesn't turn `\n` into `\r\n`.
+        lines.join("\r\n")
+    }
+}
+
+impl Frontend for Terminal {
+    fn draw(&mut self) -> MainLoopAction {
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            match event::read() {
+                Ok(event) => match self.handle_event(event) {
+                    MainLoopAction::Continue => {}
+                    action => return action,
+                },
+                Err(_) => return MainLoopAction::Interrupt,
+            }
+        }
+        MainLoopAction::Continue
+    }
+    fn draw_points(&mut self, points: &[(i32, i32)]) {
+        let mut pixels = vec![vec![false; self.width]; self.height];
+        for &(x, y) in points {
+            pixels[y as usize][x as usize] = true;
+        }
+        let frame = self.render(&pixels);
+        let status = self.status.as_deref().unwrap_or("");
+        let [r, g, b] = self.palette.foreground;
+        let foreground = Color::Rgb { r, g, b };
+        let [r, g, b] = self.palette.background;
+        let background = Color::Rgb { r, g, b };
+        let result = queue!(
+            self.stdout,
+            cursor::MoveTo(0, 0),
+            SetForegroundColor(foreground),
+            SetBackgroundColor(background),
+            Print(frame),
+            ResetColor,
+            Print("\r\n"),
+            terminal::Clear(ClearType::CurrentLine),
+            Print(status),
+        )
+        .and_then(|_| self.stdout.flush());
+        if let Err(e) = result {
+            eprintln!("Error drawing to the terminal: {e}");
+        }
+    }
+    fn keypad(&mut self) -> Keypad {
+        Keypad::from_fn(|key| self.is_held(KeyCode::Char(self.keymap.char_for(key))))
+    }
+    fn set_resolution(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        let _ = execute!(self.stdout, terminal::Clear(ClearType::All));
+    }
+    fn set_title(&mut self, title: &str) {
+        let _ = execute!(self.stdout, terminal::SetTitle(format!("chip8 - {title}")));
+    }
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+    fn is_fast_forward_held(&mut self) -> bool {
+        self.is_held(KeyCode::Tab)
+    }
+    fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.reports_releases {
+            let _ = execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(
+            self.stdout,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = terminal::disable_raw_mode();
+    }
+}
@@ -1,302 +1,917 @@
 use std::{
-    io::stdin,
-    process,
-    sync::{Arc, Mutex},
+    fs,
+    ops::Range,
+    path::PathBuf,
+    rc::Rc,
+    str::FromStr,
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use rand::random;
+use blocks::{Block, BlockCache};
 
 use crate::{
-    audio::Audio,
-    screen::{MainLoopAction, Screen, SCREEN_HEIGHT, SCREEN_WIDTH},
+    analysis,
+    audio::AudioSink,
+    font::{Font, FontSet, DEFAULT_FONT_ADDRESS, FONT_LEN},
+    framebuffer::Framebuffer,
+    frontend::{Frontend, MainLoopAction, Palette},
+    keypad::Keypad,
+    platform::{Platform, Quirks},
+    recorder::{RecordConfig, Recorder},
+    rng::{self, Rng, RngMode},
+    romdb,
+    screen::{HIRES_SCREEN_HEIGHT, SCREEN_HEIGHT, SCREEN_WIDTH},
+    screenshot::{self, ScreenshotConfig},
 };
 
+mod blocks;
+pub mod lockstep;
+#[cfg(test)]
+mod tests;
+
+const STATE_MAGIC: &[u8; 8] = b"CHIP8ST1";
+
+pub const DEFAULT_FAST_FORWARD: u32 = 4;
+pub const DEFAULT_SLOW_MOTION: u32 = 4;
+
+/// Settings given on the command line. They take precedence over the ROM
+/// database, which takes precedence over the platform's defaults.
+pub struct Options {
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub speed: Option<u32>,
+    /// Seeds `CXNN`'s random numbers, random if not given.
+    pub seed: Option<u64>,
+    pub rng: RngMode,
+    pub engine: Engine,
+    /// The glyphs `FX29` points at.
+    pub font: Font,
+    /// Where the font goes, below the program.
+    pub font_address: u16,
+    pub palette: Option<Palette>,
+    /// Where F5 saves the machine state and F9 loads it from.
+    pub state_file: Option<PathBuf>,
+    /// Frames run per frame shown while fast-forwarding.
+    pub fast_forward: u32,
+    /// How many times longer frames last in slow motion.
+    pub slow_motion: u32,
+    /// Where F12 saves screenshots.
+    pub screenshot: Option<ScreenshotConfig>,
+    pub record: Option<RecordConfig>,
+}
+
+/// How the VM runs instructions. All of them behave the same.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Engine {
+    /// Decodes every instruction each time it runs.
+    #[default]
+    Interpreter,
+    /// Decodes each instruction once and keeps the result, see `Vm::decode`.
+    Cached,
+    /// Translates straight-line code into blocks run without decoding, see
+    /// `blocks::Block`.
+    Blocks,
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Engine, String> {
+        match s {
+            "interpreter" => Ok(Engine::Interpreter),
+            "cached" => Ok(Engine::Cached),
+            "blocks" => Ok(Engine::Blocks),
+            _ => Err(format!(
+                "unknown engine `{s}`, expected interpreter, cached or blocks"
+            )),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            platform: None,
+            quirks: None,
+            speed: None,
+            seed: None,
+            rng: RngMode::default(),
+            engine: Engine::default(),
+            font: *FontSet::default().font(),
+            font_address: DEFAULT_FONT_ADDRESS,
+            palette: None,
+            state_file: None,
+            fast_forward: DEFAULT_FAST_FORWARD,
+            slow_motion: DEFAULT_SLOW_MOTION,
+            screenshot: None,
+            record: None,
+        }
+    }
+}
+
 pub struct Vm {
-    memory: [u8; 4096],
+    code: Vec<u8>,
+    memory: Vec<u8>,
     registers: [u8; 16],
     i_reg: u16,
-    delay_reg: Arc<Mutex<u8>>,
-    sound_reg: Arc<Mutex<u8>>,
+    delay_reg: u8,
+    sound_reg: u8,
     pc: u16,
     sp: u8,
     stack: [u16; 16],
-    virtual_screen: [[u8; SCREEN_WIDTH]; SCREEN_HEIGHT],
-    screen: Screen,
-    audio: Arc<Audio>,
+    screen: Framebuffer,
+    frontend: Box<dyn Frontend>,
+    audio: Box<dyn AudioSink>,
+    platform: Platform,
+    quirks: Quirks,
+    speed: u32,
+    rng_mode: RngMode,
+    seed: u64,
+    rng: Box<dyn Rng>,
+    font: Font,
+    font_address: u16,
+    /// The handler for the instruction at each address once it has run,
+    /// when caching decoded instructions.
+    decoded: Option<Vec<Option<Handler>>>,
+    blocks: Option<BlockCache>,
+    /// The block `step` is in and the index of its next op.
+    block_cursor: Option<(Rc<Block>, usize)>,
+    waiting_for_vblank: bool,
+    /// Set while `FX0A` halts the program until a key is pressed and
+    /// released.
+    key_wait: Option<KeyWait>,
+    keypad: Keypad,
+    state_file: Option<PathBuf>,
+    fast_forward: u32,
+    slow_motion: u32,
+    paused: bool,
+    slowed_down: bool,
+    palette: Palette,
+    screenshot: Option<ScreenshotConfig>,
+    recorder: Option<Recorder>,
+    /// Instructions run since the machine was created.
+    instructions: u64,
 }
 
+const FRAME_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
 impl Vm {
-    const HEX_SPRITES: [u8; 80] = [
-        0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-        0x20, 0x60, 0x20, 0x20, 0x70, // 1
-        0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-        0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-        0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-        0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-        0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-        0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-        0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-        0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-        0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-        0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-        0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-        0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-        0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-        0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-    ];
-    pub fn new(code: Vec<u8>) -> Vm {
-        let len = code.len();
-        let memory: [u8; 4096] = Vm::HEX_SPRITES
-            .into_iter()
-            .chain([0; 432].into_iter())
-            .chain(code.into_iter())
-            .chain(vec![0; 4096 - 512 - len].into_iter())
-            .collect::<Vec<u8>>()
-            .try_into()
-            .unwrap();
-        Vm {
-            memory,
+    pub fn new(
+        code: Vec<u8>,
+        mut frontend: Box<dyn Frontend>,
+        audio: Box<dyn AudioSink>,
+        options: Options,
+    ) -> Result<Vm, String> {
+        let info = romdb::lookup(&code);
+        let platform = options
+            .platform
+            .or(info.as_ref().and_then(|i| i.platform))
+            .unwrap_or_else(|| analysis::detect_platform(&code));
+        let quirks = options
+            .quirks
+            .or(info.as_ref().and_then(|i| i.quirks))
+            .unwrap_or(platform.quirks());
+        let speed = options
+            .speed
+            .or(info.as_ref().and_then(|i| i.speed))
+            .unwrap_or(platform.speed());
+        if let Some(info) = &info {
+            frontend.set_title(&info.full_title());
+            frontend.set_key_hints(info.key_hints.clone());
+        }
+        let palette = options
+            .palette
+            .or(info.as_ref().and_then(|i| i.palette))
+            .unwrap_or_default();
+        frontend.set_palette(palette);
+        let recorder = options
+            .record
+            .as_ref()
+            .map(|config| Recorder::new(config, palette))
+            .transpose()?;
+        let memory_size = platform.memory_size();
+        if code.len() > memory_size - 0x200 {
+            return Err(format!(
+                "ROM is too large for {platform}: {} bytes, at most {} fit",
+                code.len(),
+                memory_size - 0x200
+            ));
+        }
+        if options.font_address as usize + FONT_LEN > 0x200 {
+            return Err(format!(
+                "no room for the font at {:#05X}, it has to end by 0x200",
+                options.font_address
+            ));
+        }
+        let seed = options.seed.unwrap_or_else(rand::random);
+        let mut vm = Vm {
+            code,
+            memory: vec![0; memory_size],
             registers: [0; 16],
             i_reg: 0,
-            delay_reg: Arc::new(Mutex::new(0)),
-            sound_reg: Arc::new(Mutex::new(0)),
+            delay_reg: 0,
+            sound_reg: 0,
             pc: 0x200,
             sp: 0,
             stack: [0; 16],
-            virtual_screen: [[0; SCREEN_WIDTH]; SCREEN_HEIGHT],
-            screen: Screen::new(),
-            audio: Arc::new(Audio::new()),
-        }
+            screen: Framebuffer::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+            frontend,
+            audio,
+            platform,
+            quirks,
+            speed,
+            rng_mode: options.rng,
+            seed,
+            rng: rng::new(options.rng, seed),
+            font: options.font,
+            font_address: options.font_address,
+            decoded: (options.engine == Engine::Cached).then(|| vec![None; memory_size]),
+            blocks: (options.engine == Engine::Blocks).then(|| BlockCache::new(memory_size)),
+            block_cursor: None,
+            waiting_for_vblank: false,
+            key_wait: None,
+            keypad: Keypad::default(),
+            state_file: options.state_file,
+            fast_forward: options.fast_forward.max(1),
+            slow_motion: options.slow_motion.max(1),
+            paused: false,
+            slowed_down: false,
+            palette,
+            screenshot: options.screenshot,
+            recorder,
+            instructions: 0,
+        };
+        vm.reset();
+        Ok(vm)
+    }
+    /// Puts the machine back in its power-on state with the ROM loaded.
+    pub fn reset(&mut self) {
+        self.memory.fill(0);
+        let font_address = self.font_address as usize;
+        self.memory[font_address..font_address + FONT_LEN].copy_from_slice(&self.font);
+        self.memory[0x200..0x200 + self.code.len()].copy_from_slice(&self.code);
+        self.registers = [0; 16];
+        self.i_reg = 0;
+        self.delay_reg = 0;
+        self.sound_reg = 0;
+        self.pc = 0x200;
+        self.sp = 0;
+        self.stack = [0; 16];
+        self.screen = Framebuffer::new(
+            SCREEN_WIDTH,
+            match self.platform {
+                Platform::VipHires => HIRES_SCREEN_HEIGHT,
+                _ => SCREEN_HEIGHT,
+            },
+        );
+        self.waiting_for_vblank = false;
+        self.key_wait = None;
+        self.rng = rng::new(self.rng_mode, self.seed);
+        self.invalidate(0, self.memory.len());
+        self.frontend
+            .set_resolution(self.screen.width(), self.screen.height());
+        self.audio.reset();
     }
-    pub fn start(mut self) {
-        let sound_reg = Arc::clone(&self.sound_reg);
-        let delay_reg = Arc::clone(&self.delay_reg);
-        let audio = Arc::clone(&self.audio);
-        thread::spawn(move || loop {
-            let mut sound_reg = sound_reg.lock().unwrap();
-            let mut delay_reg = delay_reg.lock().unwrap();
-            if *sound_reg > 0 {
-                audio.play();
-                *sound_reg -= 1;
+    /// Runs the program until the frontend stops it, or until it fails.
+    pub fn start(&mut self) -> Result<(), String> {
+        let mut next_frame = Instant::now();
+        let result = 'main: loop {
+            let mut step = false;
+            match self.frontend.draw() {
+                MainLoopAction::Interrupt => break Ok(()),
+                MainLoopAction::SaveState => self.save_state_file(),
+                MainLoopAction::LoadState => self.load_state_file(),
+                MainLoopAction::TogglePause => self.paused = !self.paused,
+                MainLoopAction::FrameAdvance => {
+                    // When running, the first press only pauses.
+                    step = self.paused;
+                    self.paused = true;
+                }
+                MainLoopAction::ToggleSlowMotion => self.slowed_down = !self.slowed_down,
+                MainLoopAction::Reset => self.reset(),
+                MainLoopAction::Screenshot => self.save_screenshot(),
+                MainLoopAction::Continue => {}
             }
-            if *delay_reg > 0 {
-                *delay_reg -= 1;
+            self.keypad = self.frontend.keypad();
+            let frames = if step {
+                1
+            } else if self.paused {
+                0
+            } else if self.frontend.is_fast_forward_held() {
+                self.fast_forward
+            } else {
+                1
+            };
+            for _ in 0..frames {
+                if let Err(e) = self.run_frame() {
+                    break 'main Err(e);
+                }
+                self.tick_timers();
+                self.record_frame();
             }
-            thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
-        });
-        loop {
-            match self.screen.draw() {
-                MainLoopAction::Interrupt => break,
-                MainLoopAction::Continue => {}
+            if self.paused {
+                self.audio.set_playing(false);
             }
-            match self.run() {
-                MainLoopAction::Interrupt => break,
-                MainLoopAction::Continue => {}
+            self.update_status(frames);
+            self.draw_points();
+            if !self.frontend.is_realtime() {
+                continue;
+            }
+            next_frame += if self.slowed_down {
+                FRAME_PERIOD * self.slow_motion
+            } else {
+                FRAME_PERIOD
+            };
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else if now - next_frame > FRAME_PERIOD * 4 {
+                // Emulation was paused (e.g. by the help overlay), don't
+                // replay the missed frames all at once.
+                next_frame = now;
+            }
+        };
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(e) = recorder.finish() {
+                eprintln!("Error recording: {e}");
             }
         }
+        result
     }
-    fn next_instruction(&mut self) -> u16 {
-        let high_byte = self.memory[self.pc as usize];
-        self.pc += 1;
-        let low_byte = self.memory[self.pc as usize];
-        self.pc += 1;
-        ((high_byte as u16) << 8) | (low_byte as u16)
+    fn record_frame(&mut self) {
+        let Some(recorder) = &mut self.recorder else {
+            return;
+        };
+        let rows = self.screen.to_bytes();
+        let screen: Vec<&[u8]> = rows.iter().map(Vec::as_slice).collect();
+        if let Err(e) = recorder.frame(&screen) {
+            eprintln!("Error recording, stopping: {e}");
+            self.recorder = None;
+        }
     }
-    fn draw_generic_sprite(&mut self, instruction: u16) {
-        let vx_register_index = instruction >> 8 & 0x000F;
-        let vy_register_index = instruction >> 4 & 0x000F;
-        let sprite_height = instruction & 0x000F;
-        let pixels_to_draw = &self.memory[self.i_reg.into()..(self.i_reg + sprite_height).into()];
-        let vx = self.registers[vx_register_index as usize];
-        let vy = self.registers[vy_register_index as usize];
-        let mut bit_erased = false;
-        for i in 0..(sprite_height as u8) {
-            for j in 0..8 {
-                let screen_x = ((vx + j) % SCREEN_WIDTH as u8) as usize;
-                let screen_y = ((vy + i) % SCREEN_HEIGHT as u8) as usize;
-                let current_pixel = &mut self.virtual_screen[screen_y][screen_x];
-                let new_pixel = ((pixels_to_draw[i as usize] >> (7 - j)) & 1) ^ *current_pixel;
-                if *current_pixel == 1 && new_pixel == 0 {
-                    bit_erased = true;
+    fn update_status(&mut self, frames: u32) {
+        let status = if self.paused {
+            Some("PAUSED".to_string())
+        } else if frames > 1 {
+            Some(format!("FAST x{frames}"))
+        } else if self.slowed_down {
+            Some(format!("SLOW x1/{}", self.slow_motion))
+        } else {
+            None
+        };
+        self.frontend.set_status(status);
+    }
+    /// Saves the display as PNGs, see `screenshot::save`.
+    pub fn screenshot(&self) -> Result<Vec<PathBuf>, String> {
+        let Some(config) = &self.screenshot else {
+            return Err("no screenshot directory given".to_string());
+        };
+        let rows = self.screen.to_bytes();
+        let screen: Vec<&[u8]> = rows.iter().map(Vec::as_slice).collect();
+        screenshot::save(config, &screen, self.palette)
+    }
+    fn save_screenshot(&self) {
+        match self.screenshot() {
+            Ok(paths) => {
+                for path in paths {
+                    eprintln!("Saved {}", path.display());
                 }
-                *current_pixel = new_pixel;
             }
+            Err(e) => eprintln!("Could not save screenshot: {e}"),
         }
-        if bit_erased {
-            self.registers[0xF] = 1;
-        } else {
-            self.registers[0xF] = 0;
+    }
+    /// Serializes everything needed to resume the program later.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = STATE_MAGIC.to_vec();
+        state.extend_from_slice(&(self.memory.len() as u32).to_be_bytes());
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.registers);
+        state.extend_from_slice(&self.i_reg.to_be_bytes());
+        state.push(self.delay_reg);
+        state.push(self.sound_reg);
+        state.extend_from_slice(&self.pc.to_be_bytes());
+        state.push(self.sp);
+        for address in self.stack {
+            state.extend_from_slice(&address.to_be_bytes());
+        }
+        state.push(self.screen.height() as u8);
+        // One byte per pixel for all 64 rows, whatever the height.
+        for y in 0..HIRES_SCREEN_HEIGHT {
+            state.extend(
+                (0..SCREEN_WIDTH)
+                    .map(|x| (y < self.screen.height() && self.screen.pixel(x, y)) as u8),
+            );
         }
-        self.draw_points();
+        state
     }
-    fn draw_points(&mut self) {
-        let mut points = vec![];
-        for (i, row) in self.virtual_screen.iter().enumerate() {
-            for (j, pixel) in row.iter().enumerate() {
-                if *pixel == 1 {
-                    points.push((j as i32, i as i32));
+    /// Restores a state made by `save_state` for a ROM on the same platform.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let mut reader = StateReader(state);
+        if reader.take(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err("not a chip8 state file".to_string());
+        }
+        let memory_size = u32::from_be_bytes(reader.take(4)?.try_into().unwrap()) as usize;
+        if memory_size != self.memory.len() {
+            return Err(format!(
+                "state has {memory_size} bytes of memory, {} expected",
+                self.memory.len()
+            ));
+        }
+        self.memory.copy_from_slice(reader.take(memory_size)?);
+        self.invalidate(0, memory_size);
+        self.registers.copy_from_slice(reader.take(16)?);
+        self.i_reg = reader.u16()?;
+        self.delay_reg = reader.u8()?;
+        self.sound_reg = reader.u8()?;
+        self.pc = reader.u16()?;
+        self.sp = reader.u8()?;
+        if self.sp as usize > self.stack.len() {
+            return Err(format!("invalid stack pointer {}", self.sp));
+        }
+        for address in &mut self.stack {
+            *address = reader.u16()?;
+        }
+        // A program waiting on `FX0A` is saved with `pc` on the `FX0A`, so
+        // it goes back to waiting for a fresh press.
+        self.key_wait = None;
+        let height = match reader.u8()? as usize {
+            height @ (SCREEN_HEIGHT | HIRES_SCREEN_HEIGHT) => height,
+            height => return Err(format!("invalid screen height {height}")),
+        };
+        self.screen = Framebuffer::new(SCREEN_WIDTH, height);
+        for y in 0..HIRES_SCREEN_HEIGHT {
+            let row = reader.take(SCREEN_WIDTH)?;
+            if y < height {
+                for (x, &pixel) in row.iter().enumerate() {
+                    self.screen.set_pixel(x, y, pixel == 1);
                 }
             }
         }
-        self.screen.draw_points(&points[..]);
+        self.frontend
+            .set_resolution(self.screen.width(), self.screen.height());
+        self.audio.set_playing(self.sound_reg > 0);
+        Ok(())
+    }
+    pub fn save_state_file(&self) {
+        let Some(file) = &self.state_file else {
+            eprintln!("No state file given, not saving");
+            return;
+        };
+        if let Err(e) = fs::write(file, self.save_state()) {
+            eprintln!("Could not save state to {}: {e}", file.display());
+        }
+    }
+    pub fn load_state_file(&mut self) {
+        let Some(file) = self.state_file.clone() else {
+            eprintln!("No state file given, not loading");
+            return;
+        };
+        let result = fs::read(&file)
+            .map_err(|e| e.to_string())
+            .and_then(|state| self.load_state(&state));
+        if let Err(e) = result {
+            eprintln!("Could not load state from {}: {e}", file.display());
+        }
+    }
+    /// How many instructions have run since the machine was created. Jumps
+    /// to themselves skipped for the rest of a frame by the blocks engine
+    /// aren't counted, as they don't run.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
     }
-    fn run(&mut self) -> MainLoopAction {
+    /// The display as text, `#` for lit pixels and `.` for dark ones.
+    pub fn screen_text(&self) -> String {
+        let mut text = String::new();
+        for y in 0..self.screen.height() {
+            text.extend((0..self.screen.width()).map(|x| {
+                if self.screen.pixel(x, y) {
+                    '#'
+                } else {
+                    '.'
+                }
+            }));
+            text.push('\n');
+        }
+        text
+    }
+    fn run_frame(&mut self) -> Result<(), String> {
+        if self.blocks.is_some() {
+            self.run_blocks()?;
+            self.waiting_for_vblank = false;
+            return Ok(());
+        }
+        for _ in 0..self.speed {
+            self.run()?;
+            if self.waiting_for_vblank || self.key_wait.is_some() {
+                break;
+            }
+        }
+        self.waiting_for_vblank = false;
+        Ok(())
+    }
+    /// Runs a frame's worth of instructions a block at a time, stopping at
+    /// the same instruction as `run_frame`.
+    fn run_blocks(&mut self) -> Result<(), String> {
+        let mut remaining = self.speed as usize;
+        while remaining > 0 {
+            let pc = self.pc as usize;
+            let block = match &mut self.blocks {
+                Some(blocks) if pc < self.memory.len() => {
+                    blocks.block(pc, &self.memory, self.platform)
+                }
+                _ => Rc::new(Block {
+                    ops: vec![],
+                    spins: false,
+                }),
+            };
+            if block.ops.is_empty() {
+                // Not a whole instruction, let `run` deal with it.
+                self.run()?;
+                remaining -= 1;
+            } else if block.spins {
+                remaining = 0;
+            } else {
+                let mut ran = 0;
+                for op in block.ops.iter().take(remaining) {
+                    let address = self.pc;
+                    self.pc = address.wrapping_add(2);
+                    self.execute(op.handler, op.instruction, address)?;
+                    ran += 1;
+                    if self.pc != op.next {
+                        break;
+                    }
+                }
+                remaining -= ran;
+            }
+            if self.waiting_for_vblank || self.key_wait.is_some() {
+                break;
+            }
+        }
+        Ok(())
+    }
+    /// Runs one instruction the way the engine runs it, for `lockstep`. The
+    /// blocks engine goes through its blocks an op at a time, but runs a
+    /// block that spins rather than skipping the rest of the frame.
+    fn step(&mut self) -> Result<(), String> {
+        let pc = self.pc;
+        let (block, index) = match (self.block_cursor.take(), &mut self.blocks) {
+            (Some((block, index)), _)
+                if index < block.ops.len() && block.ops[index - 1].next == pc =>
+            {
+                (block, index)
+            }
+            (_, Some(blocks)) if (pc as usize) < self.memory.len() => {
+                (blocks.block(pc as usize, &self.memory, self.platform), 0)
+            }
+            _ => return self.run(),
+        };
+        let Some(op) = block.ops.get(index) else {
+            // Not a whole instruction, let `run` deal with it.
+            return self.run();
+        };
+        let (handler, instruction) = (op.handler, op.instruction);
+        self.block_cursor = Some((block, index + 1));
+        self.pc = pc.wrapping_add(2);
+        self.execute(handler, instruction, pc)
+    }
+    fn tick_timers(&mut self) {
+        self.rng.tick();
+        self.audio.end_frame();
+        self.delay_reg = self.delay_reg.saturating_sub(1);
+        self.sound_reg = self.sound_reg.saturating_sub(1);
+        self.audio.set_playing(self.sound_reg > 0);
+    }
+    fn next_instruction(&mut self) -> Result<u16, String> {
+        let pc = self.pc as usize;
+        let (Some(&high_byte), Some(&low_byte)) = (self.memory.get(pc), self.memory.get(pc + 1))
+        else {
+            return Err(format!("pc {pc:#05X} ran past the end of memory"));
+        };
+        self.pc = self.pc.wrapping_add(2);
+        Ok(((high_byte as u16) << 8) | (low_byte as u16))
+    }
+    fn skip_next_instruction(&mut self) {
+        let pc = self.pc as usize;
+        // XO-CHIP's `F000 NNNN` is 4 bytes long and skipped as a whole.
+        if self.platform == Platform::XoChip && self.memory.get(pc..pc + 2) == Some(&[0xF0, 0x00]) {
+            self.pc = self.pc.wrapping_add(4);
+        } else {
+            self.pc = self.pc.wrapping_add(2);
+        }
+    }
+    fn draw_generic_sprite(&mut self, instruction: u16) -> Result<(), String> {
+        let vx_register_index = instruction >> 8 & 0x000F;
+        let vy_register_index = instruction >> 4 & 0x000F;
+        let sprite_height = instruction & 0x000F;
+        let sprite = self.memory_range(self.i_reg as usize, sprite_height as usize)?;
+        let pixels_to_draw = &self.memory[sprite];
+        let vx = self.registers[vx_register_index as usize] as usize;
+        let vy = self.registers[vy_register_index as usize] as usize;
+        let bit_erased = self
+            .screen
+            .draw_sprite(vx, vy, pixels_to_draw, self.quirks.clip_sprites);
+        self.registers[0xF] = bit_erased as u8;
+        self.waiting_for_vblank = self.quirks.display_wait;
+        Ok(())
+    }
+    /// Runs `FX0A` once. Like the COSMAC VIP, the key is only stored once it
+    /// has been released again; until then `pc` stays on the `FX0A` so it
+    /// runs again next frame, leaving drawing, timers and hotkeys working.
+    fn wait_for_key(&mut self, register_index: usize) {
+        self.key_wait = match self.key_wait {
+            Some(KeyWait::Release(key)) if !self.keypad.is_pressed(key) => {
+                self.registers[register_index] = key;
+                None
+            }
+            Some(KeyWait::Release(key)) => Some(KeyWait::Release(key)),
+            _ => Some(
+                self.keypad
+                    .first_pressed()
+                    .map_or(KeyWait::Press, KeyWait::Release),
+            ),
+        };
+        if self.key_wait.is_some() {
+            self.pc = self.pc.wrapping_sub(2);
+        }
+    }
+    fn draw_points(&mut self) {
+        let points: Vec<(i32, i32)> = self
+            .screen
+            .lit_pixels()
+            .map(|(x, y)| (x as i32, y as i32))
+            .collect();
+        self.frontend.draw_points(&points[..]);
+    }
+    /// Whether `run` knows `instruction` on `platform`, rather than failing
+    /// on it.
+    pub fn is_implemented(instruction: u16, platform: Platform) -> bool {
+        Vm::decode(instruction, platform).is_some()
+    }
+    fn run(&mut self) -> Result<(), String> {
         // println!("Press enter to read an instruction...");
         // let mut buffer = String::new();
         // stdin().read_line(&mut buffer).unwrap();
         // if buffer.trim() == "q" {
         //     process::exit(0);
         // }
-        let instruction = self.next_instruction();
-        match instruction {
-            0x00E0 => self.screen.clear(),
-            0x00EE => {
-                self.sp -= 1;
-                self.pc = self.stack[self.sp as usize];
-            }
-            0x1000..=0x1FFF => {
-                self.pc = instruction & 0x0FFF;
-            }
-            0x2000..=0x2FFF => {
+        let address = self.pc;
+        let mut instruction = self.next_instruction()?;
+        if self.platform == Platform::VipHires && self.pc == 0x202 && instruction == 0x1260 {
+            // Skip the VIP's hires interpreter patch, it lives at 0x260-0x2BF
+            // and the program proper starts at 0x2C0.
+            instruction = 0x12C0;
+        }
+        let handler = match &mut self.decoded {
+            Some(decoded) => *decoded[address as usize]
+                .get_or_insert_with(|| Vm::handler(instruction, self.platform)),
+            None => Vm::handler(instruction, self.platform),
+        };
+        self.execute(handler, instruction, address)
+        // println!(" == Vm State == ");
+        // println!("instruction: {}", format!("{:#06x}", instruction));
+        // println!(
+        //     "bytes: {:?}",
+        //     &self.memory[self.i_reg as usize..(self.i_reg + 16) as usize]
+        //         .iter()
+        //         .map(|x| format!("{:08b}", x))
+        //         .collect::<Vec<_>>()
+        // );
+        // println!(
+        //     "registers: {:?}",
+        //     self.registers
+        //         .iter()
+        //         .map(|x| format!("{:04x}", x))
+        //         .collect::<Vec<_>>()
+        // );
+        // println!("I register: {:?}", format!("{:04x}", self.i_reg));
+        // println!("pc: {:?}", format!("{:04x}", self.pc));
+        // println!("virtual screen: {:?}", self.virtual_screen);
+    }
+    /// Runs `handler` for `instruction`, fetched from `address`, saying where
+    /// it failed if it does.
+    fn execute(&mut self, handler: Handler, instruction: u16, address: u16) -> Result<(), String> {
+        self.instructions += 1;
+        handler(self, instruction)
+            .map_err(|e| format!("{e}, running {instruction:04X} at {address:#05X}"))
+    }
+    /// The code running `instruction`, or `unknown` if there is none on
+    /// `platform`.
+    fn handler(instruction: u16, platform: Platform) -> Handler {
+        Vm::decode(instruction, platform).unwrap_or(unknown)
+    }
+    /// The code running `instruction`, given the instruction again so the
+    /// handlers for a whole family of opcodes can find their operands, or
+    /// `None` if `platform` has no such instruction.
+    fn decode(instruction: u16, platform: Platform) -> Option<Handler> {
+        let handler: Handler = match instruction {
+            0x00E0 => |vm, _| {
+                vm.screen.clear();
+                Ok(())
+            },
+            0x0230 if platform == Platform::VipHires => |vm, _| {
+                vm.screen.clear();
+                Ok(())
+            },
+            0x00EE => |vm, _| {
+                if vm.sp == 0 {
+                    return Err("stack underflow".to_string());
+                }
+                vm.sp -= 1;
+                vm.pc = vm.stack[vm.sp as usize];
+                Ok(())
+            },
+            0x1000..=0x1FFF => |vm, instruction| {
+                vm.pc = instruction & 0x0FFF;
+                Ok(())
+            },
+            0x2000..=0x2FFF => |vm, instruction| {
                 let address = instruction & 0x0FFF;
-                self.stack[self.sp as usize] = self.pc;
-                self.sp += 1;
-                self.pc = address;
-            }
-            0x3000..=0x3FFF => {
+                if vm.sp as usize == vm.stack.len() {
+                    return Err("stack overflow".to_string());
+                }
+                vm.stack[vm.sp as usize] = vm.pc;
+                vm.sp += 1;
+                vm.pc = address;
+                Ok(())
+            },
+            0x3000..=0x3FFF => |vm, instruction| {
                 let register_index = (instruction >> 8 & 0x000F) as usize;
                 let value = (instruction & 0x00FF) as u8;
-                if self.registers[register_index] == value {
-                    self.pc += 2;
+                if vm.registers[register_index] == value {
+                    vm.skip_next_instruction();
                 }
-            }
-            0x4000..=0x4FFF => {
+                Ok(())
+            },
+            0x4000..=0x4FFF => |vm, instruction| {
                 let register_index = (instruction >> 8 & 0x000F) as usize;
                 let value = (instruction & 0x00FF) as u8;
-                if self.registers[register_index] != value {
-                    self.pc += 2;
+                if vm.registers[register_index] != value {
+                    vm.skip_next_instruction();
                 }
-            }
+                Ok(())
+            },
             instruction
                 if upper_first_byte(instruction) == 5 && lower_second_byte(instruction) == 0 =>
             {
-                let register_index_x = (instruction >> 8 & 0x000F) as usize;
-                let register_index_y = (instruction >> 4 & 0x000F) as usize;
-                if self.registers[register_index_x] == self.registers[register_index_y] {
-                    self.pc += 2;
+                |vm, instruction| {
+                    let register_index_x = (instruction >> 8 & 0x000F) as usize;
+                    let register_index_y = (instruction >> 4 & 0x000F) as usize;
+                    if vm.registers[register_index_x] == vm.registers[register_index_y] {
+                        vm.skip_next_instruction();
+                    }
+                    Ok(())
                 }
             }
-            0x6000..=0x6FFF => {
+            0x6000..=0x6FFF => |vm, instruction| {
                 let register_index = (instruction >> 8 & 0x000F) as usize;
                 let value = instruction & 0x00FF;
-                self.registers[register_index] = value as u8;
-            }
-            0x7000..=0x7FFF => {
+                vm.registers[register_index] = value as u8;
+                Ok(())
+            },
+            0x7000..=0x7FFF => |vm, instruction| {
                 let register_index = instruction >> 8 & 0x000F;
                 let value = instruction & 0x00FF;
-                self.registers[register_index as usize] += value as u8;
-            }
+                let register = &mut vm.registers[register_index as usize];
+                *register = register.wrapping_add(value as u8);
+                Ok(())
+            },
             instruction if upper_first_byte(instruction) == 8 => {
                 match lower_second_byte(instruction) {
-                    0 => {
+                    0 => |vm, instruction| {
                         let register_index_x = (instruction >> 8 & 0x000F) as usize;
                         let register_index_y = (instruction >> 4 & 0x000F) as usize;
-                        self.registers[register_index_x] = self.registers[register_index_y];
-                    }
-                    1 => {
+                        vm.registers[register_index_x] = vm.registers[register_index_y];
+                        Ok(())
+                    },
+                    1 => |vm, instruction| {
                         let register_index_x = (instruction >> 8 & 0x000F) as usize;
                         let register_index_y = (instruction >> 4 & 0x000F) as usize;
-                        self.registers[register_index_x] |= self.registers[register_index_y];
-                    }
-                    2 => {
+                        vm.registers[register_index_x] |= vm.registers[register_index_y];
+                        if vm.quirks.vf_reset {
+                            vm.registers[0xF] = 0;
+                        }
+                        Ok(())
+                    },
+                    2 => |vm, instruction| {
                         let register_index_x = (instruction >> 8 & 0x000F) as usize;
                         let register_index_y = (instruction >> 4 & 0x000F) as usize;
-                        self.registers[register_index_x] &= self.registers[register_index_y];
-                    }
-                    3 => {
+                        vm.registers[register_index_x] &= vm.registers[register_index_y];
+                        if vm.quirks.vf_reset {
+                            vm.registers[0xF] = 0;
+                        }
+                        Ok(())
+                    },
+                    3 => |vm, instruction| {
                         let register_index_x = (instruction >> 8 & 0x000F) as usize;
                         let register_index_y = (instruction >> 4 & 0x000F) as usize;
-                        self.registers[register_index_x] ^= self.registers[register_index_y];
-                    }
-                    4 => {
+                        vm.registers[register_index_x] ^= vm.registers[register_index_y];
+                        if vm.quirks.vf_reset {
+                            vm.registers[0xF] = 0;
+                        }
+                        Ok(())
+                    },
+                    4 => |vm, instruction| {
                         let register_index_x = (instruction >> 8 & 0x000F) as usize;
                         let register_index_y = (instruction >> 4 & 0x000F) as usize;
-                        let sum = self.registers[register_index_x] as u16
-                            + self.registers[register_index_y] as u16;
-                        if sum > 255 {
-                            self.registers[0xF] = 1;
-                        } else {
-                            self.registers[0xF] = 0;
-                        }
-                        self.registers[register_index_x] += self.registers[register_index_y];
-                    }
-                    5 => {
+                        let (sum, carry) = vm.registers[register_index_x]
+                            .overflowing_add(vm.registers[register_index_y]);
+                        // VF is set last, so it holds the carry even as VX.
+                        vm.registers[register_index_x] = sum;
+                        vm.registers[0xF] = carry as u8;
+                        Ok(())
+                    },
+                    5 => |vm, instruction| {
+                        let register_index_x = (instruction >> 8 & 0x000F) as usize;
+                        let register_index_y = (instruction >> 4 & 0x000F) as usize;
+                        let (difference, borrow) = vm.registers[register_index_x]
+                            .overflowing_sub(vm.registers[register_index_y]);
+                        vm.registers[register_index_x] = difference;
+                        vm.registers[0xF] = !borrow as u8;
+                        Ok(())
+                    },
+                    6 => |vm, instruction| {
                         let register_index_x = (instruction >> 8 & 0x000F) as usize;
                         let register_index_y = (instruction >> 4 & 0x000F) as usize;
-                        if self.registers[register_index_x] > self.registers[register_index_y] {
-                            self.registers[0xF] = 1;
+                        let value = if vm.quirks.shift_vx {
+                            vm.registers[register_index_x]
                         } else {
-                            self.registers[0xF] = 0;
-                        }
-                        self.registers[register_index_x] -= self.registers[register_index_y];
-                    }
-                    6 => {
+                            vm.registers[register_index_y]
+                        };
+                        vm.registers[register_index_x] = value >> 1;
+                        vm.registers[0xF] = value & 1;
+                        Ok(())
+                    },
+                    7 => |vm, instruction| {
                         let register_index_x = (instruction >> 8 & 0x000F) as usize;
-                        self.registers[0xF] = self.registers[register_index_x] & 1;
-                        self.registers[register_index_x] >>= 1;
-                    }
-                    7 => {
+                        let register_index_y = (instruction >> 4 & 0x000F) as usize;
+                        let (difference, borrow) = vm.registers[register_index_y]
+                            .overflowing_sub(vm.registers[register_index_x]);
+                        vm.registers[register_index_x] = difference;
+                        vm.registers[0xF] = !borrow as u8;
+                        Ok(())
+                    },
+                    0xE => |vm, instruction| {
                         let register_index_x = (instruction >> 8 & 0x000F) as usize;
                         let register_index_y = (instruction >> 4 & 0x000F) as usize;
-                        if self.registers[register_index_y] > self.registers[register_index_x] {
-                            self.registers[0xF] = 1;
+                        let value = if vm.quirks.shift_vx {
+                            vm.registers[register_index_x]
                         } else {
-                            self.registers[0xF] = 0;
-                        }
-                        self.registers[register_index_x] =
-                            self.registers[register_index_y] - self.registers[register_index_x];
-                    }
-                    0xE => {
-                        let register_index_x = (instruction >> 8 & 0x000F) as usize;
-                        self.registers[0xF] = self.registers[register_index_x] >> 7 & 1;
-                        self.registers[register_index_x] <<= 1;
-                    }
-                    _ => panic!("Unknown instruction: {instruction}"),
+                            vm.registers[register_index_y]
+                        };
+                        vm.registers[register_index_x] = value << 1;
+                        vm.registers[0xF] = value >> 7;
+                        Ok(())
+                    },
+                    _ => return None,
                 }
             }
             instruction
                 if upper_first_byte(instruction) == 9 && lower_second_byte(instruction) == 0 =>
             {
-                let register_index_x = (instruction >> 8 & 0x000F) as usize;
-                let register_index_y = (instruction >> 4 & 0x000F) as usize;
-                if self.registers[register_index_x] != self.registers[register_index_y] {
-                    self.pc += 2;
+                |vm, instruction| {
+                    let register_index_x = (instruction >> 8 & 0x000F) as usize;
+                    let register_index_y = (instruction >> 4 & 0x000F) as usize;
+                    if vm.registers[register_index_x] != vm.registers[register_index_y] {
+                        vm.skip_next_instruction();
+                    }
+                    Ok(())
                 }
             }
-            0xA000..=0xAFFF => {
-                self.i_reg = instruction & 0x0FFF;
-            }
-            0xB000..=0xBFFF => {
+            0xA000..=0xAFFF => |vm, instruction| {
+                vm.i_reg = instruction & 0x0FFF;
+                Ok(())
+            },
+            0xB000..=0xBFFF => |vm, instruction| {
                 let address = instruction & 0x0FFF;
-                self.pc = self.registers[0] as u16 + address;
-            }
-            0xC000..=0xCFFF => {
+                let register_index = if vm.quirks.jump_vx {
+                    (instruction >> 8 & 0x000F) as usize
+                } else {
+                    0
+                };
+                vm.pc = vm.registers[register_index] as u16 + address;
+                Ok(())
+            },
+            0xC000..=0xCFFF => |vm, instruction| {
                 let register_index = (instruction >> 8 & 0x000F) as usize;
                 let value = instruction & 0x00FF;
-                self.registers[register_index] = random::<u8>() & value as u8;
-            }
-            0xD000..=0xDFFF => self.draw_generic_sprite(instruction),
+                vm.registers[register_index] = vm.rng.next_byte() & value as u8;
+                Ok(())
+            },
+            0xD000..=0xDFFF => |vm, instruction| vm.draw_generic_sprite(instruction),
             instruction
                 if upper_first_byte(instruction) == 0xE
                     && upper_second_byte(instruction) == 9
                     && lower_second_byte(instruction) == 0xE =>
             {
-                let register_index = (instruction >> 8 & 0x000F) as usize;
-                let register_value = self.registers[register_index];
-                if self.screen.is_key_pressed(register_value) {
-                    self.pc += 2;
+                |vm, instruction| {
+                    let register_index = (instruction >> 8 & 0x000F) as usize;
+                    let register_value = vm.registers[register_index];
+                    if vm.keypad.is_pressed(register_value) {
+                        vm.skip_next_instruction();
+                    }
+                    Ok(())
                 }
             }
             instruction
@@ -304,91 +919,166 @@ impl Vm {
                     && upper_second_byte(instruction) == 0xA
                     && lower_second_byte(instruction) == 1 =>
             {
-                let register_index = (instruction >> 8 & 0x000F) as usize;
-                let register_value = self.registers[register_index];
-                if !self.screen.is_key_pressed(register_value) {
-                    self.pc += 2;
+                |vm, instruction| {
+                    let register_index = (instruction >> 8 & 0x000F) as usize;
+                    let register_value = vm.registers[register_index];
+                    if !vm.keypad.is_pressed(register_value) {
+                        vm.skip_next_instruction();
+                    }
+                    Ok(())
                 }
             }
-            instruction if upper_first_byte(instruction) == 0xF => {
-                if upper_second_byte(instruction) == 0 && lower_second_byte(instruction) == 7 {
+            0xF000 if platform == Platform::XoChip => |vm, _| {
+                let bytes = vm.memory_range(vm.pc as usize, 2)?;
+                let high_byte = vm.memory[bytes.start];
+                let low_byte = vm.memory[bytes.start + 1];
+                vm.i_reg = ((high_byte as u16) << 8) | low_byte as u16;
+                vm.pc = vm.pc.wrapping_add(2);
+                Ok(())
+            },
+            0xF002 if platform == Platform::XoChip => |vm, _| {
+                let pattern = vm.memory_range(vm.i_reg as usize, 16)?;
+                let pattern = vm.memory[pattern].try_into().unwrap();
+                vm.audio.set_pattern(pattern);
+                Ok(())
+            },
+            instruction if upper_first_byte(instruction) == 0xF => match instruction & 0x00FF {
+                0x07 => |vm, instruction| {
                     let register_index = (instruction >> 8 & 0x000F) as usize;
-                    self.registers[register_index] = *self.delay_reg.lock().unwrap();
-                }
-                if upper_second_byte(instruction) == 0 && lower_second_byte(instruction) == 0xA {
+                    vm.registers[register_index] = vm.delay_reg;
+                    Ok(())
+                },
+                0x0A => |vm, instruction| {
                     let register_index = (instruction >> 8 & 0x000F) as usize;
-                    match self.screen.wait_for_keypress() {
-                        Some(key) => {
-                            self.registers[register_index] = key;
-                        }
-                        None => return MainLoopAction::Interrupt,
-                    }
-                }
-                if upper_second_byte(instruction) == 1 && lower_second_byte(instruction) == 5 {
+                    vm.wait_for_key(register_index);
+                    Ok(())
+                },
+                0x15 => |vm, instruction| {
                     let register_index = (instruction >> 8 & 0x000F) as usize;
-                    self.delay_reg = Arc::new(Mutex::new(self.registers[register_index]));
-                }
-                if upper_second_byte(instruction) == 1 && lower_second_byte(instruction) == 8 {
+                    vm.delay_reg = vm.registers[register_index];
+                    Ok(())
+                },
+                0x18 => |vm, instruction| {
                     let register_index = (instruction >> 8 & 0x000F) as usize;
-                    self.sound_reg = Arc::new(Mutex::new(self.registers[register_index]));
-                }
-                if upper_second_byte(instruction) == 1 && lower_second_byte(instruction) == 0xE {
+                    vm.sound_reg = vm.registers[register_index];
+                    vm.audio.set_playing(vm.sound_reg > 0);
+                    Ok(())
+                },
+                0x1E => |vm, instruction| {
                     let register_index = (instruction >> 8 & 0x000F) as usize;
-                    self.i_reg += self.registers[register_index] as u16;
-                }
-                if upper_second_byte(instruction) == 2 && lower_second_byte(instruction) == 9 {
+                    vm.i_reg = vm.i_reg.wrapping_add(vm.registers[register_index] as u16);
+                    Ok(())
+                },
+                0x29 => |vm, instruction| {
                     let register_index = (instruction >> 8 & 0x000F) as usize;
-                    let value = self.registers[register_index];
-                    self.i_reg = value as u16 * 5;
-                }
-                if upper_second_byte(instruction) == 3 && lower_second_byte(instruction) == 3 {
+                    let value = vm.registers[register_index];
+                    vm.i_reg = vm.font_address + value as u16 * 5;
+                    Ok(())
+                },
+                0x33 => |vm, instruction| {
                     let register_index = (instruction >> 8 & 0x000F) as usize;
-                    let value = self.registers[register_index];
-                    let hundreds = (value / 100) * 100;
-                    let tens = ((value - hundreds) / 10) * 10;
-                    let ones = value - hundreds - tens;
-                    let i_reg = self.i_reg as usize;
-                    self.memory[i_reg] = hundreds;
-                    self.memory[i_reg + 1] = tens;
-                    self.memory[i_reg + 2] = ones;
-                }
-                if upper_second_byte(instruction) == 5 && lower_second_byte(instruction) == 5 {
+                    let value = vm.registers[register_index];
+                    let digits = vm.memory_range(vm.i_reg as usize, 3)?;
+                    vm.memory[digits.clone()].copy_from_slice(&[
+                        value / 100,
+                        value / 10 % 10,
+                        value % 10,
+                    ]);
+                    vm.invalidate(digits.start, digits.len());
+                    Ok(())
+                },
+                0x3A if platform == Platform::XoChip => |vm, instruction| {
                     let register_index = (instruction >> 8 & 0x000F) as usize;
-                    for reg in 0..=register_index {
-                        self.memory[self.i_reg as usize + reg] = self.registers[reg];
+                    vm.audio.set_pitch(vm.registers[register_index]);
+                    Ok(())
+                },
+                0x55 => |vm, instruction| {
+                    let register_index = (instruction >> 8 & 0x000F) as usize;
+                    let bytes = vm.memory_range(vm.i_reg as usize, register_index + 1)?;
+                    vm.memory[bytes.clone()].copy_from_slice(&vm.registers[..=register_index]);
+                    vm.invalidate(bytes.start, bytes.len());
+                    if vm.quirks.increment_i {
+                        vm.i_reg = vm.i_reg.wrapping_add(register_index as u16 + 1);
                     }
-                }
-                if upper_second_byte(instruction) == 6 && lower_second_byte(instruction) == 5 {
+                    Ok(())
+                },
+                0x65 => |vm, instruction| {
                     let register_index = (instruction >> 8 & 0x000F) as usize;
-                    for reg in 0..=register_index {
-                        self.registers[reg] = self.memory[self.i_reg as usize + reg];
+                    let bytes = vm.memory_range(vm.i_reg as usize, register_index + 1)?;
+                    vm.registers[..=register_index].copy_from_slice(&vm.memory[bytes]);
+                    if vm.quirks.increment_i {
+                        vm.i_reg = vm.i_reg.wrapping_add(register_index as u16 + 1);
                     }
-                }
+                    Ok(())
+                },
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(handler)
+    }
+    /// The range of `len` bytes of memory at `address`, or an error if it
+    /// runs past the end.
+    fn memory_range(&self, address: usize, len: usize) -> Result<Range<usize>, String> {
+        if address + len > self.memory.len() {
+            return Err(format!(
+                "{len} bytes at {address:#05X} go past the end of memory"
+            ));
+        }
+        Ok(address..address + len)
+    }
+    /// Forgets the decoded instructions overlapping `len` bytes written at
+    /// `address`, so self-modifying code runs what it wrote.
+    fn invalidate(&mut self, address: usize, len: usize) {
+        self.block_cursor = None;
+        if let Some(blocks) = &mut self.blocks {
+            blocks.invalidate(address, len);
+        }
+        if let Some(decoded) = &mut self.decoded {
+            let end = (address + len).min(decoded.len());
+            // The instruction starting the byte before also covers `address`.
+            for entry in &mut decoded[address.saturating_sub(1)..end] {
+                *entry = None;
             }
-            instruction => panic!("Unknown instruction: {instruction}"),
         }
-        // println!(" == Vm State == ");
-        // println!("instruction: {}", format!("{:#06x}", instruction));
-        // println!(
-        //     "bytes: {:?}",
-        //     &self.memory[self.i_reg as usize..(self.i_reg + 16) as usize]
-        //         .iter()
-        //         .map(|x| format!("{:08b}", x))
-        //         .collect::<Vec<_>>()
-        // );
-        // println!(
-        //     "registers: {:?}",
-        //     self.registers
-        //         .iter()
-        //         .map(|x| format!("{:04x}", x))
-        //         .collect::<Vec<_>>()
-        // );
-        // println!("I register: {:?}", format!("{:04x}", self.i_reg));
-        // println!("pc: {:?}", format!("{:04x}", self.pc));
-        // println!("virtual screen: {:?}", self.virtual_screen);
-        MainLoopAction::Continue
     }
 }
+
+/// Runs one instruction, see `Vm::decode`.
+type Handler = fn(&mut Vm, u16) -> Result<(), String>;
+
+/// Runs an instruction `Vm::decode` doesn't know.
+fn unknown(_: &mut Vm, _: u16) -> Result<(), String> {
+    Err("unknown instruction".to_string())
+}
+
+#[derive(Clone, Copy)]
+enum KeyWait {
+    /// No key is down yet.
+    Press,
+    /// `key` went down, waiting for it to come back up.
+    Release(u8),
+}
+
+struct StateReader<'a>(&'a [u8]);
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < len {
+            return Err("state file is truncated".to_string());
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+}
+
 fn upper_first_byte(instruction: u16) -> u8 {
     (instruction >> 12 & 0x000F) as u8
 }
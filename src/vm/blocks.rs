@@ -0,0 +1,116 @@
+use std::rc::Rc;
+
+use super::{upper_first_byte, Handler, Vm};
+use crate::platform::Platform;
+
+// Keeps loops from being unrolled into huge blocks.
+const MAX_BLOCK_LEN: usize = 64;
+
+/// Code translated once into its handlers, run without fetching or decoding.
+///
+/// Translation follows jumps and calls and assumes skips aren't taken, so a
+/// block runs until `pc` goes somewhere else. It ends for good with the first
+/// instruction that returns, writes memory, draws or waits, so the VM only has
+/// to look at its state between blocks.
+pub(super) struct Block {
+    pub(super) ops: Vec<Op>,
+    /// Just a jump to itself, the usual way to stop a program. Running it
+    /// changes nothing, so the rest of the frame can be skipped.
+    pub(super) spins: bool,
+}
+
+pub(super) struct Op {
+    pub(super) handler: Handler,
+    pub(super) instruction: u16,
+    /// Where `pc` points after the instruction if the block goes on.
+    pub(super) next: u16,
+}
+
+/// The blocks translated so far, by start address.
+pub(super) struct BlockCache {
+    blocks: Vec<Option<Rc<Block>>>,
+    /// The bytes some block was translated from.
+    code: Vec<bool>,
+}
+
+impl BlockCache {
+    pub(super) fn new(memory_size: usize) -> BlockCache {
+        BlockCache {
+            blocks: vec![None; memory_size],
+            code: vec![false; memory_size],
+        }
+    }
+    /// The block starting at `address`, translated from `memory` if needed.
+    /// Empty if there is no whole instruction at `address`.
+    pub(super) fn block(&mut self, address: usize, memory: &[u8], platform: Platform) -> Rc<Block> {
+        if let Some(block) = &self.blocks[address] {
+            return block.clone();
+        }
+        let (block, addresses) = translate(address, memory, platform);
+        for address in addresses {
+            self.code[address..address + 2].fill(true);
+        }
+        let block = Rc::new(block);
+        self.blocks[address] = Some(block.clone());
+        block
+    }
+    /// Drops every block if `len` bytes written at `address` hit translated
+    /// code. Writing to code is rare enough not to track which blocks it
+    /// hits.
+    pub(super) fn invalidate(&mut self, address: usize, len: usize) {
+        let end = (address + len).min(self.code.len());
+        if self.code[address.min(end)..end].contains(&true) {
+            self.blocks.fill(None);
+            self.code.fill(false);
+        }
+    }
+}
+
+/// Translates the block at `address`, also returning the addresses of its
+/// instructions.
+fn translate(mut address: usize, memory: &[u8], platform: Platform) -> (Block, Vec<usize>) {
+    let mut ops = vec![];
+    let mut addresses = vec![];
+    while address + 1 < memory.len() && ops.len() < MAX_BLOCK_LEN {
+        let mut instruction = (memory[address] as u16) << 8 | memory[address + 1] as u16;
+        if platform == Platform::VipHires && address == 0x200 && instruction == 0x1260 {
+            // See `Vm::run`.
+            instruction = 0x12C0;
+        }
+        let next = match upper_first_byte(instruction) {
+            0x1 | 0x2 => instruction & 0x0FFF,
+            // XO-CHIP's `F000 NNNN` is 4 bytes long.
+            0xF if instruction == 0xF000 && platform == Platform::XoChip => {
+                (address as u16).wrapping_add(4)
+            }
+            _ => (address as u16).wrapping_add(2),
+        };
+        ops.push(Op {
+            handler: Vm::handler(instruction, platform),
+            instruction,
+            next,
+        });
+        addresses.push(address);
+        if ends_block(instruction) || addresses.contains(&(next as usize)) {
+            break;
+        }
+        address = next as usize;
+    }
+    let spins = match &ops[..] {
+        [op] => op.next as usize == addresses[0] && upper_first_byte(op.instruction) == 0x1,
+        _ => false,
+    };
+    (Block { ops, spins }, addresses)
+}
+
+/// Whether `instruction` goes somewhere translation can't follow, writes
+/// memory, changes what the VM waits for or isn't known.
+fn ends_block(instruction: u16) -> bool {
+    match upper_first_byte(instruction) {
+        0x0 => !matches!(instruction, 0x00E0 | 0x0230),
+        0x8 => !matches!(instruction & 0xF, 0..=7 | 0xE),
+        0xB | 0xD => true,
+        0xF => matches!(instruction & 0xFF, 0x0A | 0x33 | 0x55),
+        _ => false,
+    }
+}
@@ -0,0 +1,219 @@
+//! Runs two machines side by side, an instruction at a time, to find where
+//! they stop behaving the same, e.g. because of a quirk.
+
+use std::{collections::VecDeque, fmt};
+
+use super::Vm;
+use crate::{disasm, keypad::Keypad};
+
+/// Instructions shown before a divergence.
+const TRACE_LEN: usize = 16;
+/// Differing bytes of memory listed before the rest are only counted.
+const MAX_LISTED_BYTES: usize = 8;
+
+/// Where two machines first differed.
+pub struct Divergence {
+    /// Instructions each had run.
+    pub instructions: u64,
+    /// The first machine's frame.
+    pub frame: u64,
+    /// What differed, e.g. `V0: 01 vs 02`.
+    pub differences: Vec<String>,
+    /// The instructions leading up to it, oldest first. The state was the
+    /// same before, so both machines ran them.
+    trace: VecDeque<Traced>,
+}
+
+struct Traced {
+    frame: u64,
+    address: u16,
+    instruction: Option<u16>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Diverged after {} instructions, in frame {}:",
+            self.instructions, self.frame
+        )?;
+        for difference in &self.differences {
+            writeln!(f, "  {difference}")?;
+        }
+        writeln!(f, "Last instructions:")?;
+        for traced in &self.trace {
+            let text = match traced.instruction {
+                Some(instruction) => format!(
+                    "{instruction:04X}  {}",
+                    disasm::mnemonic(instruction, &|target| format!("{target:#05X}"))
+                        .unwrap_or_default()
+                ),
+                None => "past the end of memory".to_string(),
+            };
+            writeln!(
+                f,
+                "  frame {:<5} {:03X}  {text}",
+                traced.frame, traced.address
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A machine run one instruction at a time, ending frames where `run_frame`
+/// would.
+struct Stepper<'a> {
+    vm: &'a mut Vm,
+    frame: u64,
+    /// Instructions run this frame.
+    ran: u32,
+}
+
+impl Stepper<'_> {
+    fn step(&mut self, keypad: &mut impl FnMut(u64) -> Keypad) -> Result<(), String> {
+        self.vm.step()?;
+        self.ran += 1;
+        if self.ran == self.vm.speed || self.vm.waiting_for_vblank || self.vm.key_wait.is_some() {
+            // `run_blocks` starts a new block with each frame.
+            self.vm.block_cursor = None;
+            self.vm.waiting_for_vblank = false;
+            self.vm.tick_timers();
+            self.frame += 1;
+            self.ran = 0;
+            self.vm.keypad = keypad(self.frame);
+        }
+        Ok(())
+    }
+}
+
+/// Runs `a` and `b` for `frames` of `a`'s frames, comparing them after every
+/// instruction, with the keys `keypad` gives for each frame held down. Both
+/// run one instruction at a time through their own engine, see `Vm::step`.
+///
+/// Returns the first divergence, or an error if both machines failed the
+/// same way.
+pub fn run(
+    a: &mut Vm,
+    b: &mut Vm,
+    frames: u64,
+    mut keypad: impl FnMut(u64) -> Keypad,
+) -> Result<Option<Divergence>, String> {
+    a.keypad = keypad(0);
+    b.keypad = keypad(0);
+    let mut a = Stepper {
+        vm: a,
+        frame: 0,
+        ran: 0,
+    };
+    let mut b = Stepper {
+        vm: b,
+        frame: 0,
+        ran: 0,
+    };
+    let mut trace = VecDeque::with_capacity(TRACE_LEN);
+    let mut instructions = 0;
+    let mut differences = differences(a.vm, b.vm);
+    while differences.is_empty() && a.frame < frames {
+        if trace.len() == TRACE_LEN {
+            trace.pop_front();
+        }
+        let address = a.vm.pc;
+        let bytes = a.vm.memory.get(address as usize..address as usize + 2);
+        trace.push_back(Traced {
+            frame: a.frame,
+            address,
+            instruction: bytes.map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])),
+        });
+        instructions += 1;
+        match (a.step(&mut keypad), b.step(&mut keypad)) {
+            (Ok(()), Ok(())) => differences = self::differences(a.vm, b.vm),
+            (Err(e), Err(other)) if e == other => return Err(e),
+            (first, second) => {
+                differences = [("first", first), ("second", second)]
+                    .into_iter()
+                    .filter_map(|(which, result)| {
+                        result
+                            .err()
+                            .map(|e| format!("the {which} machine stopped: {e}"))
+                    })
+                    .collect()
+            }
+        }
+    }
+    Ok((!differences.is_empty()).then_some(Divergence {
+        instructions,
+        frame: a.frame,
+        differences,
+        trace,
+    }))
+}
+
+/// What differs between the state of `a` and `b`, nothing if they are the
+/// same.
+fn differences(a: &Vm, b: &Vm) -> Vec<String> {
+    let mut differences = vec![];
+    if a.pc != b.pc {
+        differences.push(format!("PC: {:03X} vs {:03X}", a.pc, b.pc));
+    }
+    if a.i_reg != b.i_reg {
+        differences.push(format!("I: {:03X} vs {:03X}", a.i_reg, b.i_reg));
+    }
+    for (index, (x, y)) in a.registers.iter().zip(&b.registers).enumerate() {
+        if x != y {
+            differences.push(format!("V{index:X}: {x:02X} vs {y:02X}"));
+        }
+    }
+    if a.stack[..a.sp as usize] != b.stack[..b.sp as usize] {
+        differences.push(format!(
+            "stack: {:03X?} vs {:03X?}",
+            &a.stack[..a.sp as usize],
+            &b.stack[..b.sp as usize]
+        ));
+    }
+    if a.delay_reg != b.delay_reg {
+        differences.push(format!("DT: {} vs {}", a.delay_reg, b.delay_reg));
+    }
+    if a.sound_reg != b.sound_reg {
+        differences.push(format!("ST: {} vs {}", a.sound_reg, b.sound_reg));
+    }
+    // Only memory both have, XO-CHIP has more.
+    let len = a.memory.len().min(b.memory.len());
+    if a.memory[..len] != b.memory[..len] {
+        let bytes: Vec<(usize, (&u8, &u8))> = a.memory[..len]
+            .iter()
+            .zip(&b.memory[..len])
+            .enumerate()
+            .filter(|(_, (x, y))| x != y)
+            .collect();
+        for (address, (x, y)) in bytes.iter().take(MAX_LISTED_BYTES) {
+            differences.push(format!("memory at {address:03X}: {x:02X} vs {y:02X}"));
+        }
+        if bytes.len() > MAX_LISTED_BYTES {
+            differences.push(format!(
+                "and {} more bytes of memory",
+                bytes.len() - MAX_LISTED_BYTES
+            ));
+        }
+    }
+    if a.screen != b.screen {
+        let (width, height) = (a.screen.width(), a.screen.height());
+        if (width, height) != (b.screen.width(), b.screen.height()) {
+            differences.push(format!(
+                "screen size: {width}x{height} vs {}x{}",
+                b.screen.width(),
+                b.screen.height()
+            ));
+        } else {
+            let pixels: Vec<(usize, usize)> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|&(x, y)| a.screen.pixel(x, y) != b.screen.pixel(x, y))
+                .collect();
+            differences.push(format!(
+                "screen: {} pixels differ, the first at {:?}",
+                pixels.len(),
+                pixels[0]
+            ));
+        }
+    }
+    differences
+}
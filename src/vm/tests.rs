@@ -0,0 +1,425 @@
+use std::cell::Cell;
+
+use super::*;
+use crate::{audio::NullSink, frontend::Headless};
+
+/// A VIP with no quirks running `program`.
+fn vm(program: &[u16]) -> Vm {
+    vm_with_quirks(program, Quirks::default())
+}
+
+fn vm_with_quirks(program: &[u16], quirks: Quirks) -> Vm {
+    let rom = program.iter().flat_map(|i| i.to_be_bytes()).collect();
+    let options = Options {
+        platform: Some(Platform::Vip),
+        quirks: Some(quirks),
+        seed: Some(0),
+        ..Options::default()
+    };
+    Vm::new(
+        rom,
+        Box::new(Headless::new(None)),
+        Box::new(NullSink),
+        options,
+    )
+    .unwrap()
+}
+
+/// Runs `instruction` with the registers set as given, returning them after.
+fn registers_after(instruction: u16, registers: &[(usize, u8)]) -> [u8; 16] {
+    let mut vm = vm(&[instruction]);
+    for &(index, value) in registers {
+        vm.registers[index] = value;
+    }
+    vm.run().unwrap();
+    vm.registers
+}
+
+#[test]
+fn add_sets_carry() {
+    let registers = registers_after(0x8014, &[(0, 0xFF), (1, 0x02)]);
+    assert_eq!((registers[0], registers[0xF]), (0x01, 1));
+    let registers = registers_after(0x8014, &[(0, 0x10), (1, 0x20), (0xF, 1)]);
+    assert_eq!((registers[0], registers[0xF]), (0x30, 0));
+}
+
+#[test]
+fn subtract_sets_no_borrow() {
+    let registers = registers_after(0x8015, &[(0, 5), (1, 3)]);
+    assert_eq!((registers[0], registers[0xF]), (2, 1));
+    let registers = registers_after(0x8015, &[(0, 3), (1, 5)]);
+    assert_eq!((registers[0], registers[0xF]), (0xFE, 0));
+    // No borrow when equal.
+    let registers = registers_after(0x8015, &[(0, 4), (1, 4)]);
+    assert_eq!((registers[0], registers[0xF]), (0, 1));
+}
+
+#[test]
+fn reverse_subtract_sets_no_borrow() {
+    let registers = registers_after(0x8017, &[(0, 3), (1, 5)]);
+    assert_eq!((registers[0], registers[0xF]), (2, 1));
+    let registers = registers_after(0x8017, &[(0, 5), (1, 3)]);
+    assert_eq!((registers[0], registers[0xF]), (0xFE, 0));
+    let registers = registers_after(0x8017, &[(0, 4), (1, 4)]);
+    assert_eq!((registers[0], registers[0xF]), (0, 1));
+}
+
+#[test]
+fn arithmetic_flag_overwrites_vf_result() {
+    // 0xFF + 0x01 carries, VF gets the carry rather than the sum.
+    assert_eq!(registers_after(0x8F14, &[(0xF, 0xFF), (1, 0x01)])[0xF], 1);
+    assert_eq!(registers_after(0x8F14, &[(0xF, 0x01), (1, 0x01)])[0xF], 0);
+    assert_eq!(registers_after(0x8F15, &[(0xF, 0x01), (1, 0x02)])[0xF], 0);
+    assert_eq!(registers_after(0x8F15, &[(0xF, 0x03), (1, 0x01)])[0xF], 1);
+    assert_eq!(registers_after(0x8F17, &[(0xF, 0x02), (1, 0x01)])[0xF], 0);
+    assert_eq!(registers_after(0x8F17, &[(0xF, 0x01), (1, 0x03)])[0xF], 1);
+}
+
+#[test]
+fn arithmetic_reads_vf_as_operand() {
+    let registers = registers_after(0x80F4, &[(0, 0xFE), (0xF, 0x03)]);
+    assert_eq!((registers[0], registers[0xF]), (0x01, 1));
+    let registers = registers_after(0x80F5, &[(0, 0x05), (0xF, 0x03)]);
+    assert_eq!((registers[0], registers[0xF]), (0x02, 1));
+    let registers = registers_after(0x80F7, &[(0, 0x05), (0xF, 0x03)]);
+    assert_eq!((registers[0], registers[0xF]), (0xFE, 0));
+}
+
+#[test]
+fn shifts_set_vf_last() {
+    let registers = registers_after(0x8016, &[(1, 0b1000_0011)]);
+    assert_eq!((registers[0], registers[0xF]), (0b0100_0001, 1));
+    let registers = registers_after(0x801E, &[(1, 0b1000_0011)]);
+    assert_eq!((registers[0], registers[0xF]), (0b0000_0110, 1));
+    assert_eq!(registers_after(0x8F16, &[(1, 0b10)])[0xF], 0);
+    assert_eq!(registers_after(0x8F1E, &[(1, 0b1)])[0xF], 0);
+}
+
+#[test]
+fn add_immediate_wraps_without_touching_vf() {
+    let registers = registers_after(0x7002, &[(0, 0xFF), (0xF, 0x55)]);
+    assert_eq!((registers[0], registers[0xF]), (0x01, 0x55));
+}
+
+#[test]
+fn add_to_i_goes_past_12_bits_without_touching_vf() {
+    let mut vm = vm(&[0xF01E, 0xF01E]);
+    vm.i_reg = 0xFFF;
+    vm.registers[0] = 1;
+    vm.run().unwrap();
+    assert_eq!((vm.i_reg, vm.registers[0xF]), (0x1000, 0));
+    vm.i_reg = 0xFFFF;
+    vm.run().unwrap();
+    assert_eq!(vm.i_reg, 0);
+}
+
+#[test]
+fn bcd_stores_digits() {
+    for (value, digits) in [(254, [2, 5, 4]), (107, [1, 0, 7]), (7, [0, 0, 7])] {
+        let mut vm = vm(&[0xF033]);
+        vm.registers[0] = value;
+        vm.i_reg = 0x300;
+        vm.run().unwrap();
+        assert_eq!(vm.memory[0x300..0x303], digits, "BCD of {value}");
+    }
+}
+
+#[test]
+fn bcd_past_the_end_of_memory_fails() {
+    let mut vm = vm(&[0xF033]);
+    vm.i_reg = 0xFFE;
+    assert!(vm.run().is_err());
+}
+
+#[test]
+fn draw_reports_collisions() {
+    // Draws the 0 glyph twice.
+    let mut vm = vm(&[0xD005, 0xD005]);
+    vm.run().unwrap();
+    assert_eq!(vm.registers[0xF], 0);
+    assert!(vm.screen.pixel(0, 0));
+    vm.run().unwrap();
+    assert_eq!(vm.registers[0xF], 1);
+    assert_eq!(vm.screen.lit_pixels().count(), 0);
+}
+
+#[test]
+fn draw_clips_or_wraps_at_the_edges() {
+    for (clip_sprites, lit) in [(true, false), (false, true)] {
+        let quirks = Quirks {
+            clip_sprites,
+            ..Quirks::default()
+        };
+        // An 8 pixel wide line at (60, 31).
+        let mut vm = vm_with_quirks(&[0xD011, 0xFFFF], quirks);
+        vm.registers[0] = 60;
+        vm.registers[1] = 31;
+        vm.i_reg = 0x202;
+        vm.run().unwrap();
+        assert!(vm.screen.pixel(63, 31));
+        assert_eq!(vm.screen.pixel(0, 31), lit, "clip_sprites: {clip_sprites}");
+        assert_eq!(vm.screen.pixel(3, 31), lit, "clip_sprites: {clip_sprites}");
+    }
+}
+
+#[test]
+fn draw_wraps_its_starting_position() {
+    let quirks = Quirks {
+        clip_sprites: true,
+        ..Quirks::default()
+    };
+    let mut vm = vm_with_quirks(&[0xD011, 0x8000], quirks);
+    vm.registers[0] = 64 + 3;
+    vm.registers[1] = 32 + 2;
+    vm.i_reg = 0x202;
+    vm.run().unwrap();
+    assert_eq!(vm.screen.lit_pixels().collect::<Vec<_>>(), [(3, 2)]);
+}
+
+#[test]
+fn call_and_return() {
+    // 0x200: CALL 0x204, 0x202: JP 0x202, 0x204: RET
+    let mut vm = vm(&[0x2204, 0x1202, 0x00EE]);
+    vm.run().unwrap();
+    assert_eq!((vm.pc, vm.sp, vm.stack[0]), (0x204, 1, 0x202));
+    vm.run().unwrap();
+    assert_eq!((vm.pc, vm.sp), (0x202, 0));
+}
+
+#[test]
+fn stack_overflow_fails() {
+    // Calls itself.
+    let mut vm = vm(&[0x2200]);
+    for _ in 0..16 {
+        vm.run().unwrap();
+    }
+    let error = vm.run().unwrap_err();
+    assert!(error.starts_with("stack overflow"), "{error}");
+}
+
+#[test]
+fn stack_underflow_fails() {
+    let error = vm(&[0x00EE]).run().unwrap_err();
+    assert!(error.starts_with("stack underflow"), "{error}");
+}
+
+#[test]
+fn unknown_instructions_fail() {
+    // SUPER-CHIP's FX75 and FX85, and ones no platform has.
+    for instruction in [0xF075, 0xF085, 0xF0FF, 0x8008, 0x5001, 0x0123] {
+        let error = vm(&[instruction]).run().unwrap_err();
+        assert!(error.starts_with("unknown instruction"), "{error}");
+        assert!(!Vm::is_implemented(instruction, Platform::Vip));
+    }
+    assert!(Vm::is_implemented(0xF000, Platform::XoChip));
+    assert!(!Vm::is_implemented(0xF000, Platform::Vip));
+}
+
+#[test]
+fn audio_instructions_are_xo_chip_only() {
+    for instruction in [0xF002, 0xF13A] {
+        assert!(Vm::is_implemented(instruction, Platform::XoChip));
+        for platform in [
+            Platform::Vip,
+            Platform::VipHires,
+            Platform::Chip48,
+            Platform::SuperChip,
+        ] {
+            assert!(!Vm::is_implemented(instruction, platform), "{platform}");
+        }
+        let error = vm(&[instruction]).run().unwrap_err();
+        assert!(error.starts_with("unknown instruction"), "{error}");
+    }
+}
+
+#[test]
+fn timers_count_down_once_per_frame() {
+    let mut vm = vm(&[0xF015, 0xF118, 0xF207]);
+    vm.registers[0] = 2;
+    vm.registers[1] = 1;
+    vm.run().unwrap();
+    vm.run().unwrap();
+    assert_eq!((vm.delay_reg, vm.sound_reg), (2, 1));
+    vm.tick_timers();
+    assert_eq!((vm.delay_reg, vm.sound_reg), (1, 0));
+    vm.run().unwrap();
+    assert_eq!(vm.registers[2], 1);
+    vm.tick_timers();
+    vm.tick_timers();
+    assert_eq!((vm.delay_reg, vm.sound_reg), (0, 0));
+}
+
+#[test]
+fn interpreter_is_the_default_engine() {
+    assert_eq!(Options::default().engine, Engine::Interpreter);
+    assert_eq!("cached".parse(), Ok(Engine::Cached));
+}
+
+#[test]
+fn font_goes_where_it_is_told() {
+    let mut font = [0; FONT_LEN];
+    font[5..10].copy_from_slice(&[1, 2, 3, 4, 5]);
+    let options = Options {
+        font,
+        font_address: 0x050,
+        ..Options::default()
+    };
+    let mut vm = Vm::new(
+        vec![0xF0, 0x29],
+        Box::new(Headless::new(None)),
+        Box::new(NullSink),
+        options,
+    )
+    .unwrap();
+    vm.registers[0] = 1;
+    vm.run().unwrap();
+    assert_eq!(vm.i_reg, 0x055);
+    assert_eq!(vm.memory[0x055..0x05A], [1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn font_has_to_fit_below_the_program() {
+    let options = Options {
+        font_address: 0x1C0,
+        ..Options::default()
+    };
+    let result = Vm::new(
+        vec![],
+        Box::new(Headless::new(None)),
+        Box::new(NullSink),
+        options,
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn font_can_end_right_before_the_program() {
+    let vm = |font_address| {
+        let options = Options {
+            font_address,
+            ..Options::default()
+        };
+        Vm::new(
+            vec![],
+            Box::new(Headless::new(None)),
+            Box::new(NullSink),
+            options,
+        )
+    };
+    assert!(vm(0x200 - FONT_LEN as u16).is_ok());
+    let error = vm(0x200 - FONT_LEN as u16 + 1).err().unwrap();
+    assert!(error.contains("0x1B1"), "{error}");
+}
+
+/// Keeps the pattern the VM last set, shared with the test.
+struct PatternSink(Rc<Cell<Option<[u8; 16]>>>);
+
+impl AudioSink for PatternSink {
+    fn set_playing(&mut self, _playing: bool) {}
+    fn set_pattern(&mut self, pattern: [u8; 16]) {
+        self.0.set(Some(pattern));
+    }
+    fn set_pitch(&mut self, _pitch: u8) {}
+    fn reset(&mut self) {
+        self.0.set(None);
+    }
+}
+
+#[test]
+fn reset_forgets_the_audio_pattern() {
+    let pattern = Rc::default();
+    let options = Options {
+        platform: Some(Platform::XoChip),
+        ..Options::default()
+    };
+    // LD I, 0x200; LD AUDIO
+    let mut vm = Vm::new(
+        vec![0xA2, 0x00, 0xF0, 0x02],
+        Box::new(Headless::new(None)),
+        Box::new(PatternSink(Rc::clone(&pattern))),
+        options,
+    )
+    .unwrap();
+    vm.run().unwrap();
+    vm.run().unwrap();
+    assert!(pattern.get().is_some());
+    vm.reset();
+    assert_eq!(pattern.get(), None);
+}
+
+fn keys(pressed: &[u8]) -> Keypad {
+    Keypad::from_fn(|key| pressed.contains(&key))
+}
+
+#[test]
+fn key_skips_follow_the_keypad() {
+    for (pressed, skips) in [(&[][..], false), (&[7][..], true), (&[6, 8][..], false)] {
+        for (instruction, skip) in [(0xE09E, skips), (0xE0A1, !skips)] {
+            let mut vm = vm(&[instruction]);
+            vm.registers[0] = 7;
+            vm.keypad = keys(pressed);
+            vm.run().unwrap();
+            let pc = if skip { 0x204 } else { 0x202 };
+            assert_eq!(vm.pc, pc, "{instruction:04X} with {pressed:?} held");
+        }
+    }
+}
+
+#[test]
+fn wait_for_key_stores_it_once_released() {
+    let mut vm = vm(&[0xF30A]);
+    vm.run().unwrap();
+    assert_eq!(vm.pc, 0x200);
+    vm.keypad = keys(&[0xB, 0x4]);
+    vm.run().unwrap();
+    vm.run().unwrap();
+    assert_eq!((vm.pc, vm.registers[3]), (0x200, 0));
+    // Releasing another key doesn't count.
+    vm.keypad = keys(&[0x4]);
+    vm.run().unwrap();
+    assert_eq!((vm.pc, vm.registers[3]), (0x200, 0));
+    vm.keypad = keys(&[]);
+    vm.run().unwrap();
+    assert_eq!((vm.pc, vm.registers[3]), (0x202, 0x4));
+}
+
+/// The numbers `CXFF` gives over a few frames.
+fn random_bytes(seed: u64, rng: RngMode) -> Vec<u8> {
+    let options = Options {
+        seed: Some(seed),
+        rng,
+        ..Options::default()
+    };
+    let mut vm = Vm::new(
+        vec![0xC0, 0xFF, 0x12, 0x00],
+        Box::new(Headless::new(None)),
+        Box::new(NullSink),
+        options,
+    )
+    .unwrap();
+    (0..32)
+        .map(|_| {
+            vm.run().unwrap();
+            vm.run().unwrap();
+            vm.tick_timers();
+            vm.registers[0]
+        })
+        .collect()
+}
+
+#[test]
+fn random_numbers_follow_the_seed() {
+    for rng in [RngMode::Seeded, RngMode::Vip] {
+        assert_eq!(random_bytes(1, rng), random_bytes(1, rng));
+        assert_ne!(random_bytes(1, rng), random_bytes(2, rng));
+    }
+}
+
+#[test]
+fn random_numbers_are_masked() {
+    let mut vm = vm(&[0xC00F, 0xC1F0]);
+    vm.run().unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.registers[0] & 0xF0, 0);
+    assert_eq!(vm.registers[1] & 0x0F, 0);
+}
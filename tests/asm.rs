@@ -0,0 +1,28 @@
+//! The disassembler's output has to assemble back into the same ROM.
+
+use std::{fs, path::PathBuf};
+
+use chip8::{asm, disasm};
+
+#[test]
+fn examples_round_trip() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut roms: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "ch8"))
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty());
+    for path in roms {
+        let rom = fs::read(&path).unwrap();
+        let source = disasm::disassemble(&rom);
+        let assembled = asm::assemble(&source)
+            .unwrap_or_else(|e| panic!("{} doesn't assemble: {e}", path.display()));
+        assert!(
+            assembled == rom,
+            "{} assembles to different bytes",
+            path.display()
+        );
+    }
+}
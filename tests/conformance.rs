@@ -0,0 +1,120 @@
+//! Runs the conformance ROMs in `tests/roms` under each platform's quirks and
+//! reads their results off the screen: check marks and crosses, or the
+//! digits the quirks ROM draws.
+
+use std::{fs, path::PathBuf};
+
+use chip8::{
+    asm,
+    audio::NullSink,
+    conformance::{profiles, results},
+    frontend::{Frontend, Headless},
+    keypad::KeySchedule,
+    platform::{Platform, Quirks},
+    vm::{Options, Vm},
+};
+
+const FRAMES: u64 = 200;
+
+fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(name)
+}
+
+fn run(name: &str, frontend: Box<dyn Frontend>, platform: Platform, quirks: Quirks) -> String {
+    let rom = fs::read(path(&format!("tests/roms/{name}.ch8"))).unwrap();
+    let options = Options {
+        platform: Some(platform),
+        quirks: Some(quirks),
+        seed: Some(0),
+        ..Options::default()
+    };
+    let mut vm = Vm::new(rom, frontend, Box::new(NullSink), options).unwrap();
+    vm.start().unwrap();
+    vm.screen_text()
+}
+
+fn assert_all_pass(name: &str, checks: usize, frontend: impl Fn() -> Box<dyn Frontend>) {
+    for (platform, quirks) in profiles() {
+        let screen = run(name, frontend(), platform, quirks);
+        assert_eq!(
+            results(&screen),
+            "✓".repeat(checks),
+            "{name} on {platform} with {quirks:?}:\n{screen}"
+        );
+    }
+}
+
+#[test]
+fn roms_match_their_sources() {
+    for name in ["flags", "opcodes", "quirks", "keypad"] {
+        let source = fs::read_to_string(path(&format!("tests/roms/{name}.asm"))).unwrap();
+        let rom = fs::read(path(&format!("tests/roms/{name}.ch8"))).unwrap();
+        assert!(
+            asm::assemble(&source).unwrap() == rom,
+            "tests/roms/{name}.ch8 is out of date, run `chip8 asm tests/roms/{name}.asm`"
+        );
+    }
+}
+
+#[test]
+fn flags() {
+    assert_all_pass("flags", 16, || Box::new(Headless::new(Some(FRAMES))));
+}
+
+#[test]
+fn opcodes() {
+    assert_all_pass("opcodes", 18, || Box::new(Headless::new(Some(FRAMES))));
+}
+
+#[test]
+fn quirks() {
+    for (platform, quirks) in profiles() {
+        let screen = run(
+            "quirks",
+            Box::new(Headless::new(Some(FRAMES))),
+            platform,
+            quirks,
+        );
+        let expected: String = [
+            quirks.vf_reset,
+            quirks.increment_i,
+            quirks.display_wait,
+            quirks.clip_sprites,
+            quirks.shift_vx,
+            quirks.jump_vx,
+        ]
+        .iter()
+        .map(|&on| if on { '1' } else { '0' })
+        .collect();
+        assert_eq!(
+            results(&screen),
+            expected,
+            "quirks on {platform} with {quirks:?}:\n{screen}"
+        );
+    }
+}
+
+#[test]
+fn keypad() {
+    // Holds 5 down from frame 10 to 20, then A from frame 40 to 60, as
+    // `keypad.asm` expects.
+    let keys: KeySchedule = "5@10-20,A@40-60".parse().unwrap();
+    assert_all_pass("keypad", 4, || {
+        Box::new(Headless::new(Some(FRAMES)).with_keys(keys.clone()))
+    });
+}
+
+#[test]
+fn ibm_logo() {
+    let rom = fs::read(path("examples/IBM Logo.ch8")).unwrap();
+    let mut vm = Vm::new(
+        rom,
+        Box::new(Headless::new(Some(FRAMES))),
+        Box::new(NullSink),
+        Options::default(),
+    )
+    .unwrap();
+    vm.start().unwrap();
+    let expected = fs::read_to_string(path("tests/roms/ibm-logo.txt")).unwrap();
+    assert_eq!(vm.screen_text(), expected);
+}
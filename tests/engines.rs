@@ -0,0 +1,205 @@
+//! Differential tests: every engine must run the example ROMs exactly like
+//! the interpreter, frame by frame.
+
+use std::{
+    cell::RefCell,
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+    rc::Rc,
+};
+
+use chip8::{
+    asm,
+    audio::NullSink,
+    frontend::{Frontend, Headless, MainLoopAction},
+    keypad::Keypad,
+    platform::Quirks,
+    rng::RngMode,
+    vm::{Engine, Options, Vm},
+};
+
+const FRAMES: u64 = 200;
+
+/// Presses and releases keys on a fixed schedule and keeps a hash of every
+/// frame drawn.
+struct Scripted {
+    frame: u64,
+    frames: Rc<RefCell<Vec<u64>>>,
+}
+
+impl Frontend for Scripted {
+    fn draw(&mut self) -> MainLoopAction {
+        self.frame += 1;
+        if self.frame > FRAMES {
+            MainLoopAction::Interrupt
+        } else {
+            MainLoopAction::Continue
+        }
+    }
+    fn draw_points(&mut self, points: &[(i32, i32)]) {
+        let mut hasher = DefaultHasher::new();
+        points.hash(&mut hasher);
+        self.frames.borrow_mut().push(hasher.finish());
+    }
+    fn keypad(&mut self) -> Keypad {
+        let frame = self.frame;
+        Keypad::from_fn(|key| (frame / 5 + key as u64 * 3).is_multiple_of(17))
+    }
+    fn is_realtime(&self) -> bool {
+        false
+    }
+}
+
+/// What a run looked like: the frames drawn and the final state, or the
+/// error it stopped with.
+type Run = (Vec<u64>, Result<Vec<u8>, String>);
+
+fn run(rom: &[u8], options: impl Fn() -> Options, engine: Engine) -> Run {
+    let frames = Rc::new(RefCell::new(vec![]));
+    let frontend = Scripted {
+        frame: 0,
+        frames: frames.clone(),
+    };
+    let options = Options {
+        engine,
+        ..options()
+    };
+    let mut vm = Vm::new(
+        rom.to_vec(),
+        Box::new(frontend),
+        Box::new(NullSink),
+        options,
+    )
+    .unwrap();
+    let state = vm.start().map(|()| vm.save_state());
+    let frames = frames.borrow().clone();
+    (frames, state)
+}
+
+fn examples() -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut roms: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "ch8"))
+        .collect();
+    roms.sort();
+    roms
+}
+
+fn assert_engines_agree(options: impl Fn() -> Options) {
+    for path in examples() {
+        assert_engines_agree_on(
+            &path.display().to_string(),
+            &fs::read(&path).unwrap(),
+            &options,
+        );
+    }
+}
+
+fn assert_engines_agree_on(name: &str, rom: &[u8], options: impl Fn() -> Options) {
+    let expected = run(rom, &options, Engine::Interpreter);
+    for engine in [Engine::Cached, Engine::Blocks] {
+        let actual = run(rom, &options, engine);
+        let first_difference = expected
+            .0
+            .iter()
+            .zip(&actual.0)
+            .position(|(expected, actual)| expected != actual);
+        assert_eq!(
+            first_difference, None,
+            "{engine:?} draws a different frame for {name}"
+        );
+        assert!(expected == actual, "{engine:?} ends differently for {name}");
+    }
+}
+
+#[test]
+fn engines_agree_on_examples() {
+    assert_engines_agree(|| Options {
+        seed: Some(1),
+        ..Options::default()
+    });
+}
+
+#[test]
+fn engines_agree_without_display_wait() {
+    assert_engines_agree(|| Options {
+        quirks: Some(Quirks::default()),
+        speed: Some(500),
+        seed: Some(2),
+        ..Options::default()
+    });
+}
+
+#[test]
+fn engines_agree_with_vip_rng() {
+    assert_engines_agree(|| Options {
+        rng: RngMode::Vip,
+        seed: Some(3),
+        ..Options::default()
+    });
+}
+
+#[test]
+fn engines_agree_on_self_modifying_code() {
+    // Counts V2 up by rewriting the `LD V2` it runs next, in the middle of
+    // straight-line code.
+    let source = "
+        LD V1, 0
+    loop:
+        LD V0, 0x62
+        LD I, patched
+        LD [I], V1
+        ADD V1, 1
+    patched:
+        LD V2, 0
+        CLS
+        LD F, V2
+        DRW V3, V3, 5
+        JP loop
+    ";
+    let rom = asm::assemble(source).unwrap();
+    assert_engines_agree_on("self-modifying code", &rom, || Options {
+        speed: Some(7),
+        ..Options::default()
+    });
+}
+
+#[test]
+fn skipped_spins_are_not_counted() {
+    // Three instructions, then a jump to itself for the rest of the run.
+    let rom = asm::assemble(
+        "
+        LD V0, 1
+        LD V1, 2
+        ADD V0, V1
+    end:
+        JP end
+    ",
+    )
+    .unwrap();
+    let instructions = |engine| {
+        let options = Options {
+            quirks: Some(Quirks::default()),
+            speed: Some(100),
+            engine,
+            ..Options::default()
+        };
+        let mut vm = Vm::new(
+            rom.clone(),
+            Box::new(Headless::new(Some(10))),
+            Box::new(NullSink),
+            options,
+        )
+        .unwrap();
+        vm.start().unwrap();
+        vm.instructions()
+    };
+    assert_eq!(instructions(Engine::Interpreter), 10 * 100);
+    assert_eq!(instructions(Engine::Cached), 10 * 100);
+    // The jump runs once before the blocks engine sees that it spins.
+    assert_eq!(instructions(Engine::Blocks), 4);
+}
@@ -0,0 +1,171 @@
+//! Lockstep runs of two machines, which should point at the instruction
+//! where they start to differ.
+
+use chip8::{
+    asm,
+    audio::NullSink,
+    frontend::Headless,
+    keypad::{KeySchedule, Keypad},
+    platform::{Platform, Quirks},
+    vm::{lockstep, Engine, Options, Vm},
+};
+
+fn vm(source: &str, platform: Platform, quirks: Quirks) -> Vm {
+    vm_with_engine(source, platform, quirks, Engine::Interpreter)
+}
+
+fn vm_with_engine(source: &str, platform: Platform, quirks: Quirks, engine: Engine) -> Vm {
+    let options = Options {
+        platform: Some(platform),
+        quirks: Some(quirks),
+        seed: Some(0),
+        engine,
+        ..Options::default()
+    };
+    Vm::new(
+        asm::assemble(source).unwrap(),
+        Box::new(Headless::new(None)),
+        Box::new(NullSink),
+        options,
+    )
+    .unwrap()
+}
+
+const SHIFT: &str = "
+        LD V0, 8
+        LD V1, 2
+        SHR V0, V1
+    end:
+        JP end
+";
+
+#[test]
+fn same_machines_agree() {
+    let quirks = Platform::Vip.quirks();
+    let divergence = lockstep::run(
+        &mut vm(SHIFT, Platform::Vip, quirks),
+        &mut vm(SHIFT, Platform::Vip, quirks),
+        10,
+        |_| Keypad::default(),
+    )
+    .unwrap();
+    assert!(divergence.is_none());
+}
+
+#[test]
+fn finds_the_instruction_a_quirk_changes() {
+    let shift_vx = Quirks {
+        shift_vx: true,
+        ..Quirks::default()
+    };
+    let divergence = lockstep::run(
+        &mut vm(SHIFT, Platform::Vip, Quirks::default()),
+        &mut vm(SHIFT, Platform::Vip, shift_vx),
+        10,
+        |_| Keypad::default(),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(divergence.instructions, 3);
+    assert_eq!(divergence.differences, ["V0: 01 vs 04"]);
+    assert!(divergence.to_string().contains("204  8016  SHR V0, V1"));
+}
+
+#[test]
+fn reports_a_machine_stopping() {
+    // Reads past the end of the VIP's memory, but not XO-CHIP's.
+    let source = "
+        LD I, 0xFFF
+        LD V1, [I]
+    end:
+        JP end
+    ";
+    let divergence = lockstep::run(
+        &mut vm(source, Platform::Vip, Quirks::default()),
+        &mut vm(source, Platform::XoChip, Quirks::default()),
+        10,
+        |_| Keypad::default(),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(divergence.differences.len(), 1);
+    assert!(divergence.differences[0].starts_with("the first machine stopped"));
+}
+
+#[test]
+fn machines_stopping_the_same_way_fail() {
+    let source = "DW 0xFFFF";
+    let quirks = Quirks::default();
+    let result = lockstep::run(
+        &mut vm(source, Platform::Vip, quirks),
+        &mut vm(source, Platform::Vip, quirks),
+        10,
+        |_| Keypad::default(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn both_machines_get_the_scheduled_keys() {
+    // Only reaches the shift once 5 is pressed.
+    let source = "
+        LD V0, 5
+    wait:
+        SKP V0
+        JP wait
+        LD V0, 8
+        LD V1, 2
+        SHR V0, V1
+    end:
+        JP end
+    ";
+    let shift_vx = Quirks {
+        shift_vx: true,
+        ..Quirks::default()
+    };
+    let diverge = |keys: KeySchedule| {
+        lockstep::run(
+            &mut vm(source, Platform::Vip, Quirks::default()),
+            &mut vm(source, Platform::Vip, shift_vx),
+            10,
+            |frame| keys.keypad(frame),
+        )
+        .unwrap()
+    };
+    assert!(diverge(KeySchedule::default()).is_none());
+    let divergence = diverge("5@3-4".parse().unwrap()).unwrap();
+    assert_eq!(divergence.frame, 3);
+    assert_eq!(divergence.differences, ["V0: 01 vs 04"]);
+}
+
+#[test]
+fn engines_run_their_own_code() {
+    // Runs the block at `loop`, rewrites `target` in it to `ADD V5, 1` and
+    // runs it again, so code decoded before the write would still load.
+    let source = "
+        LD V2, 0
+    again:
+        LD V0, 0
+        JP V0, loop
+    loop:
+        ADD V2, 1
+    target:
+        LD V5, 1
+        SE V2, 1
+        JP end
+        LD I, target
+        LD V0, 0x75
+        LD V1, 0x01
+        LD [I], V1
+        JP again
+    end:
+        JP end
+    ";
+    let quirks = Platform::Vip.quirks();
+    for engine in [Engine::Cached, Engine::Blocks] {
+        let mut a = vm(source, Platform::Vip, quirks);
+        let mut b = vm_with_engine(source, Platform::Vip, quirks, engine);
+        let divergence = lockstep::run(&mut a, &mut b, 10, |_| Keypad::default()).unwrap();
+        assert!(divergence.is_none(), "{engine:?}:\n{}", divergence.unwrap());
+    }
+}
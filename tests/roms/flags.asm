@@ -0,0 +1,168 @@
+; Checks the results and VF flags of 8XY4 to 8XYE, including with VF as an
+; operand, where VF has to end up holding the flag. Draws a check mark for
+; each check that passes and a cross for each that fails.
+;
+; Shifts use the same VX and VY, so that the shift quirk doesn't matter.
+
+        LD V8, 0
+        LD V9, 0
+
+; 8XY4 without and with carry
+        LD V0, 0x10
+        LD V1, 0x20
+        ADD V0, V1
+        LD V3, VF
+        LD V4, 0x30
+        LD V5, 0
+        CALL check
+        LD V0, 0xFF
+        LD V1, 0x02
+        ADD V0, V1
+        LD V3, VF
+        LD V4, 0x01
+        LD V5, 1
+        CALL check
+
+; 8XY5 without borrow, with equal operands and with borrow
+        LD V0, 5
+        LD V1, 3
+        SUB V0, V1
+        LD V3, VF
+        LD V4, 2
+        LD V5, 1
+        CALL check
+        LD V0, 4
+        LD V1, 4
+        SUB V0, V1
+        LD V3, VF
+        LD V4, 0
+        LD V5, 1
+        CALL check
+        LD V0, 3
+        LD V1, 5
+        SUB V0, V1
+        LD V3, VF
+        LD V4, 0xFE
+        LD V5, 0
+        CALL check
+
+; 8XY7 without and with borrow
+        LD V0, 3
+        LD V1, 5
+        SUBN V0, V1
+        LD V3, VF
+        LD V4, 2
+        LD V5, 1
+        CALL check
+        LD V0, 5
+        LD V1, 3
+        SUBN V0, V1
+        LD V3, VF
+        LD V4, 0xFE
+        LD V5, 0
+        CALL check
+
+; 8XY6 and 8XYE
+        LD V0, 0x83
+        LD V1, 0x83
+        SHR V0, V1
+        LD V3, VF
+        LD V4, 0x41
+        LD V5, 1
+        CALL check
+        LD V0, 0x83
+        LD V1, 0x83
+        SHL V0, V1
+        LD V3, VF
+        LD V4, 0x06
+        LD V5, 1
+        CALL check
+
+; VF as VX: the flag wins over the result
+        LD VF, 0xFF
+        LD V1, 0x01
+        ADD VF, V1
+        LD V0, VF
+        LD V3, VF
+        LD V4, 1
+        LD V5, 1
+        CALL check
+        LD VF, 1
+        LD V1, 2
+        SUB VF, V1
+        LD V0, VF
+        LD V3, VF
+        LD V4, 0
+        LD V5, 0
+        CALL check
+        LD VF, 1
+        LD V1, 3
+        SUBN VF, V1
+        LD V0, VF
+        LD V3, VF
+        LD V4, 1
+        LD V5, 1
+        CALL check
+        LD VF, 2
+        LD V1, 2
+        SHR VF, V1
+        LD V0, VF
+        LD V3, VF
+        LD V4, 0
+        LD V5, 0
+        CALL check
+        LD VF, 0x81
+        LD V1, 0x81
+        SHL VF, V1
+        LD V0, VF
+        LD V3, VF
+        LD V4, 1
+        LD V5, 1
+        CALL check
+
+; VF as VY
+        LD V0, 0xFE
+        LD VF, 0x03
+        ADD V0, VF
+        LD V3, VF
+        LD V4, 0x01
+        LD V5, 1
+        CALL check
+
+; 7XNN wraps and leaves VF alone
+        LD VF, 0x55
+        LD V0, 0xFF
+        ADD V0, 2
+        LD V3, VF
+        LD V4, 0x01
+        LD V5, 0x55
+        CALL check
+
+end:
+        JP end
+
+; Passes if V0 is V4 and V3 is V5.
+check:
+        LD VA, 1
+        SE V0, V4
+        LD VA, 0
+        SE V3, V5
+        LD VA, 0
+; Draws a check mark if VA is 1, a cross otherwise, and moves on to the
+; next place, 10 to a row.
+result:
+        LD I, pass
+        SE VA, 1
+        LD I, fail
+        DRW V8, V9, 5
+        ADD V8, 6
+        SE V8, 60
+        RET
+        LD V8, 0
+        ADD V9, 6
+        RET
+
+pass:
+        DB 0x08, 0x10, 0xA0, 0x40, 0x00
+fail:
+        DB 0x88, 0x50, 0x20, 0x50, 0x88
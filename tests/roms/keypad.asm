@@ -0,0 +1,56 @@
+; Checks FX0A, EX9E and EXA1 against keys pressed on a schedule: 5 from
+; frame 10 to 20, then A from frame 40 to 60. Draws a check mark for each
+; check that passes and a cross for each that fails; a check waiting
+; forever for a key draws nothing.
+
+        LD V8, 0
+        LD V9, 0
+
+; FX0A gets 5, once it is released
+        LD V0, K
+        LD VA, 1
+        SE V0, 5
+        LD VA, 0
+        CALL result
+        LD V1, 5
+        LD VA, 1
+        SKNP V1
+        LD VA, 0
+        CALL result
+
+; EX9E sees A go down
+        LD V1, 0xA
+wait_for_press:
+        SKP V1
+        JP wait_for_press
+        LD VA, 1
+        CALL result
+
+; EXA1 sees A come back up
+wait_for_release:
+        SKNP V1
+        JP wait_for_release
+        LD VA, 1
+        CALL result
+
+end:
+        JP end
+
+; Draws a check mark if VA is 1, a cross otherwise, and moves on to the
+; next place, 10 to a row.
+result:
+        LD I, pass
+        SE VA, 1
+        LD I, fail
+        DRW V8, V9, 5
+        ADD V8, 6
+        SE V8, 60
+        RET
+        LD V8, 0
+        ADD V9, 6
+        RET
+
+pass:
+        DB 0x08, 0x10, 0xA0, 0x40, 0x00
+fail:
+        DB 0x88, 0x50, 0x20, 0x50, 0x88
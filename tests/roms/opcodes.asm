@@ -0,0 +1,190 @@
+; Checks the instructions whose behaviour every interpreter agrees on. Draws
+; a check mark for each check that passes and a cross for each that fails.
+
+        LD V8, 0
+        LD V9, 0
+
+; 3XNN, 4XNN, 5XY0 and 9XY0, both skipping and not
+        LD V0, 5
+        LD V1, 5
+        LD V2, 6
+        LD VA, 1
+        SE V0, 5
+        LD VA, 0
+        CALL result
+        LD VA, 0
+        SE V0, 6
+        LD VA, 1
+        CALL result
+        LD VA, 1
+        SNE V0, 6
+        LD VA, 0
+        CALL result
+        LD VA, 0
+        SNE V0, 5
+        LD VA, 1
+        CALL result
+        LD VA, 1
+        SE V0, V1
+        LD VA, 0
+        CALL result
+        LD VA, 1
+        SNE V0, V2
+        LD VA, 0
+        CALL result
+
+; 7XNN
+        LD V0, 5
+        ADD V0, 0xFE
+        LD VA, 1
+        SE V0, 3
+        LD VA, 0
+        CALL result
+
+; 8XY0 to 8XY3
+        LD V1, 0x42
+        LD V0, V1
+        LD VA, 1
+        SE V0, 0x42
+        LD VA, 0
+        CALL result
+        LD V0, 0x0F
+        LD V1, 0xF0
+        OR V0, V1
+        LD VA, 1
+        SE V0, 0xFF
+        LD VA, 0
+        CALL result
+        LD V0, 0x3C
+        LD V1, 0x0F
+        AND V0, V1
+        LD VA, 1
+        SE V0, 0x0C
+        LD VA, 0
+        CALL result
+        LD V0, 0x3C
+        LD V1, 0x0F
+        XOR V0, V1
+        LD VA, 1
+        SE V0, 0x33
+        LD VA, 0
+        CALL result
+
+; 2NNN and 00EE
+        LD V0, 0
+        CALL subroutine
+        LD VA, 1
+        SE V0, 0x77
+        LD VA, 0
+        CALL result
+
+; FX55 and FX65
+        LD V0, 1
+        LD V1, 2
+        LD V2, 3
+        LD I, scratch
+        LD [I], V2
+        LD V0, 0
+        LD V1, 0
+        LD V2, 0
+        LD I, scratch
+        LD V2, [I]
+        LD VA, 1
+        SE V0, 1
+        LD VA, 0
+        SE V1, 2
+        LD VA, 0
+        SE V2, 3
+        LD VA, 0
+        CALL result
+
+; FX33
+        LD V0, 254
+        LD I, scratch
+        LD B, V0
+        LD I, scratch
+        LD V2, [I]
+        LD VA, 1
+        SE V0, 2
+        LD VA, 0
+        SE V1, 5
+        LD VA, 0
+        SE V2, 4
+        LD VA, 0
+        CALL result
+
+; FX1E
+        LD V0, 0x11
+        LD V1, 0x22
+        LD I, scratch
+        LD [I], V1
+        LD I, scratch
+        LD V0, 1
+        ADD I, V0
+        LD V0, [I]
+        LD VA, 1
+        SE V0, 0x22
+        LD VA, 0
+        CALL result
+
+; FX29, the first row of the 7
+        LD V0, 7
+        LD F, V0
+        LD V0, [I]
+        LD VA, 1
+        SE V0, 0xF0
+        LD VA, 0
+        CALL result
+
+; FX15 and FX07
+        LD V0, 30
+        LD DT, V0
+        LD V1, DT
+        LD VA, 0
+        SE V1, 0
+        LD VA, 1
+        CALL result
+
+; DXYN collisions, drawn below the results
+        LD I, dot
+        LD V0, 56
+        LD V1, 30
+        DRW V0, V1, 1
+        LD V3, VF
+        DRW V0, V1, 1
+        LD VA, 1
+        SE V3, 0
+        LD VA, 0
+        SE VF, 1
+        LD VA, 0
+        CALL result
+
+end:
+        JP end
+
+subroutine:
+        LD V0, 0x77
+        RET
+
+; Draws a check mark if VA is 1, a cross otherwise, and moves on to the
+; next place, 10 to a row.
+result:
+        LD I, pass
+        SE VA, 1
+        LD I, fail
+        DRW V8, V9, 5
+        ADD V8, 6
+        SE V8, 60
+        RET
+        LD V8, 0
+        ADD V9, 6
+        RET
+
+pass:
+        DB 0x08, 0x10, 0xA0, 0x40, 0x00
+fail:
+        DB 0x88, 0x50, 0x20, 0x50, 0x88
+dot:
+        DB 0x80
+scratch:
+        DB 0, 0, 0
@@ -0,0 +1,94 @@
+; Shows which quirks the interpreter has, as a row of 0 and 1 digits in the
+; order of `Quirks`' fields: vf_reset, increment_i, display_wait,
+; clip_sprites, shift_vx and jump_vx.
+
+        LD V8, 0
+        LD V9, 0
+
+; vf_reset: 8XY1 clears VF
+        LD VF, 1
+        LD V0, 0
+        OR V0, V0
+        LD VA, 1
+        SE VF, 0
+        LD VA, 0
+        CALL show
+
+; increment_i: FX55 moves I past the register stored, so FX65 reads the
+; next byte
+        LD I, increment_data
+        LD V0, 0
+        LD [I], V0
+        LD V0, [I]
+        LD VA, V0
+        CALL show
+
+; display_wait: four sprites take at least three frames
+        LD I, dot
+        LD V0, 10
+        LD DT, V0
+        LD V1, 20
+        DRW V1, V1, 1
+        DRW V1, V1, 1
+        DRW V1, V1, 1
+        DRW V1, V1, 1
+        LD V0, DT
+        LD V2, 8
+        SUB V0, V2
+        LD VA, 0
+        SE VF, 1
+        LD VA, 1
+        CALL show
+
+; clip_sprites: a sprite at the right edge doesn't wrap onto a pixel on the
+; left
+        LD I, dot
+        LD V0, 0
+        LD V1, 31
+        DRW V0, V1, 1
+        LD I, three_dots
+        LD V2, 63
+        DRW V2, V1, 1
+        LD VA, 1
+        SUB VA, VF
+        DRW V2, V1, 1
+        LD I, dot
+        DRW V0, V1, 1
+        CALL show
+
+; shift_vx: 8XY6 shifts VX rather than VY
+        LD V0, 8
+        LD V1, 2
+        SHR V0, V1
+        LD VA, 0
+        SE V0, 1
+        LD VA, 1
+        CALL show
+
+; jump_vx: BXNN adds VX, here V2, rather than V0
+        LD VA, 0
+        LD V0, 0
+        LD V2, 2
+        JP V0, jump_table
+jump_table:
+        JP jump_done
+        LD VA, 1
+jump_done:
+        CALL show
+
+end:
+        JP end
+
+; Draws the digit in VA and moves on to the next place.
+show:
+        LD F, VA
+        DRW V8, V9, 5
+        ADD V8, 6
+        RET
+
+dot:
+        DB 0x80
+three_dots:
+        DB 0xE0
+increment_data:
+        DB 0, 1